@@ -0,0 +1,102 @@
+use criterion::{
+    criterion_group,
+    criterion_main,
+    BenchmarkId,
+    Criterion,
+};
+use parking_lot::Mutex;
+use skeleton_sprint_launcher::{
+    level::{
+        LEVEL_HEIGHT,
+        LEVEL_WIDTH,
+    },
+    retry::RetryPolicy,
+    sync::{
+        copy_items,
+        ItemCopy,
+        SteamWorkshopSyncState,
+    },
+};
+use std::{
+    path::Path,
+    sync::Arc,
+};
+use tokio::runtime::Runtime as TokioRuntime;
+
+/// Makes a playable level with a spawn, an exit and a block under the spawn.
+fn make_level() -> String {
+    let mut rows = vec![vec!["00"; LEVEL_WIDTH]; LEVEL_HEIGHT];
+    rows[16][1] = "P0";
+    rows[16][30] = "E0";
+    rows[17][5] = "B0";
+    rows.iter().map(|row| row.join(",") + "\n").collect()
+}
+
+/// Makes items installed as single level files in the dir, to be synced to a list dir in it.
+fn make_item_copies(dir: &Path, len: usize) -> Vec<ItemCopy> {
+    let install_dir = dir.join("install");
+    std::fs::create_dir_all(&install_dir).expect("failed to create install dir");
+    let data = make_level();
+
+    (0..len)
+        .map(|i| {
+            let install_path = install_dir.join(format!("{}.txt", i));
+            std::fs::write(&install_path, &data).expect("failed to write level");
+            ItemCopy {
+                published_file_id: i as u64,
+                title: format!("Level {}", i),
+                name: format!("Level {}", i),
+                time_updated: 0,
+                install_path,
+                size: data.len() as u64,
+                list_dir: dir.join("sync").join("subscribed"),
+            }
+        })
+        .collect()
+}
+
+/// Benchmarks syncing a few hundred items one at a time and concurrently.
+fn bench_copy_items(criterion: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!(
+        "skeleton-sprint-launcher-copy-items-bench-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let item_copies = make_item_copies(&dir, 300);
+    let mut tokio_rt = TokioRuntime::new().expect("failed to start tokio");
+
+    let mut group = criterion.benchmark_group("copy_items");
+    group.sample_size(10);
+    for concurrency in [1, 8].iter() {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(concurrency),
+            concurrency,
+            |b, &concurrency| {
+                b.iter(|| {
+                    let steam_workshop_sync_state =
+                        Arc::new(Mutex::new(SteamWorkshopSyncState::Starting));
+                    let total_bytes = item_copies.iter().map(|item_copy| item_copy.size).sum();
+                    steam_workshop_sync_state
+                        .lock()
+                        .begin_sync(item_copies.len(), total_bytes);
+
+                    tokio_rt
+                        .block_on(copy_items(
+                            item_copies.clone(),
+                            &dir,
+                            concurrency,
+                            &RetryPolicy::default(),
+                            &steam_workshop_sync_state,
+                        ))
+                        .expect("failed to copy items")
+                })
+            },
+        );
+    }
+    group.finish();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+criterion_group!(benches, bench_copy_items);
+criterion_main!(benches);
@@ -0,0 +1,732 @@
+use crate::{
+    config::{
+        Config,
+        SyncList,
+    },
+    level::{
+        Level,
+        MAX_PREVIEW_TILE_SIZE,
+    },
+    pack::{
+        export_pack,
+        get_default_pack_path,
+        get_pack_sources,
+        import_pack,
+    },
+    sync::{
+        ensure_sync_dir,
+        get_sync_manifest_path,
+        repair_sync_dir,
+        spawn_steam_workshop_sync,
+        verify_sync_dir,
+        RepairReport,
+        SteamWorkshopSyncState,
+        SyncManifest,
+        VerifyReport,
+    },
+    workshop::WorkshopCache,
+};
+use argh::FromArgs;
+use serde::Serialize;
+use std::{
+    error::Error as StdError,
+    fmt::Display,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::runtime::Runtime as TokioRuntime;
+
+/// How often steam callbacks are run while waiting for a headless sync.
+const SYNC_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+/// The exit code of verify when files are broken and were not all repaired.
+const VERIFY_BROKEN_EXIT_CODE: i32 = 2;
+
+/// A Game Launcher for Skeleton Sprint. Opens the launcher window if no command is given.
+#[derive(Debug, FromArgs)]
+pub struct Options {
+    /// the path to the config file
+    #[argh(option, default = "PathBuf::from(crate::CONFIG_PATH)")]
+    pub config: PathBuf,
+
+    /// print command output as json
+    #[argh(switch)]
+    pub json: bool,
+
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    Sync(SyncOptions),
+    Launch(LaunchOptions),
+    ListLevels(ListLevelsOptions),
+    CheckConfig(CheckConfigOptions),
+    Diagnostics(DiagnosticsOptions),
+    Preview(PreviewOptions),
+    ImportPack(ImportPackOptions),
+    ExportPack(ExportPackOptions),
+    Verify(VerifyOptions),
+}
+
+/// Sync subscribed workshop items to the sync dir and exit
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "sync")]
+pub struct SyncOptions {}
+
+/// Launch the game or levelbuilder
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "launch")]
+pub struct LaunchOptions {
+    /// what to launch, either 'game' or 'levelbuilder'
+    #[argh(positional)]
+    pub profile: LaunchProfile,
+}
+
+/// List the levels in the sync dir
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "list-levels")]
+pub struct ListLevelsOptions {}
+
+/// Load and validate the config file
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "check-config")]
+pub struct CheckConfigOptions {}
+
+/// Export a zip of the config, logs and sync state for debugging
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "diagnostics")]
+pub struct DiagnosticsOptions {
+    /// where to write the zip. Defaults to the diagnostics dir in the data dir.
+    #[argh(option)]
+    pub output: Option<PathBuf>,
+
+    /// remove the user's home dir and name from the bundle
+    #[argh(switch)]
+    pub redact: bool,
+}
+
+/// Render a preview of a level file to a png
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "preview")]
+pub struct PreviewOptions {
+    /// the level file to render
+    #[argh(positional)]
+    pub level: PathBuf,
+
+    /// where to write the png
+    #[argh(option, short = 'o')]
+    pub output: PathBuf,
+
+    /// the size of a tile in pixels
+    #[argh(option, default = "crate::level::PREVIEW_TILE_SIZE")]
+    pub tile_size: u32,
+}
+
+/// Install the levels of a level pack into the sync dir
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "import-pack")]
+pub struct ImportPackOptions {
+    /// the pack to install
+    #[argh(positional)]
+    pub pack: PathBuf,
+}
+
+/// Export level files as a level pack
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "export-pack")]
+pub struct ExportPackOptions {
+    /// the level files to put in the pack
+    #[argh(positional)]
+    pub levels: Vec<PathBuf>,
+
+    /// where to write the pack. Defaults to the packs dir in the data dir.
+    #[argh(option, short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+/// Check the synced levels against the checksums from the last sync, exiting with 2 if any are still broken
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "verify")]
+pub struct VerifyOptions {
+    /// copy missing and modified levels again from steam
+    #[argh(switch)]
+    pub repair: bool,
+}
+
+/// A launchable program from the config.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchProfile {
+    Game,
+    Levelbuilder,
+}
+
+impl FromStr for LaunchProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "game" => Ok(Self::Game),
+            "levelbuilder" => Ok(Self::Levelbuilder),
+            _ => Err(format!(
+                "Unknown profile '{}', expected 'game' or 'levelbuilder'",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SyncOutput {
+    synced: usize,
+    files: Vec<SyncedFile>,
+}
+
+/// A level file written by a sync.
+#[derive(Debug, Serialize)]
+struct SyncedFile {
+    published_file_id: u64,
+
+    /// The level file in the item's install folder, if the item was installed as a folder.
+    source: Option<PathBuf>,
+    path: PathBuf,
+}
+
+impl std::fmt::Display for SyncOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Synced {} item(s)", self.synced)?;
+        for file in self.files.iter() {
+            if let Some(source) = file.source.as_ref() {
+                write!(
+                    f,
+                    "\n{}: '{}' -> '{}'",
+                    file.published_file_id,
+                    source.display(),
+                    file.path.display()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LaunchOutput {
+    profile: LaunchProfile,
+    path: PathBuf,
+}
+
+impl std::fmt::Display for LaunchOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Launched '{}'", self.path.display())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ListLevelsOutput {
+    levels: Vec<LevelEntry>,
+}
+
+#[derive(Debug, Serialize)]
+struct LevelEntry {
+    name: String,
+    list: SyncList,
+    path: PathBuf,
+}
+
+impl std::fmt::Display for ListLevelsOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, level) in self.levels.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{} ({})", level.name, level.list.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CheckConfigOutput {
+    path: PathBuf,
+    version: u32,
+    workshop_sync_path: PathBuf,
+    game_path: PathBuf,
+    levelbuilder_path: PathBuf,
+}
+
+impl std::fmt::Display for CheckConfigOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "'{}' is a valid version {} config",
+            self.path.display(),
+            self.version
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DiagnosticsOutput {
+    path: PathBuf,
+    redacted: bool,
+}
+
+impl std::fmt::Display for DiagnosticsOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Wrote diagnostics to '{}'", self.path.display())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PreviewOutput {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+}
+
+impl std::fmt::Display for PreviewOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Wrote {}x{} preview to '{}'",
+            self.width,
+            self.height,
+            self.path.display()
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ImportPackOutput {
+    levels: Vec<PathBuf>,
+}
+
+impl std::fmt::Display for ImportPackOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Installed {} level(s)", self.levels.len())?;
+        for path in self.levels.iter() {
+            write!(f, "\n{}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ExportPackOutput {
+    path: PathBuf,
+    levels: usize,
+}
+
+impl std::fmt::Display for ExportPackOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Wrote {} level(s) to '{}'",
+            self.levels,
+            self.path.display()
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct VerifyOutput {
+    #[serde(flatten)]
+    report: VerifyReport,
+
+    #[serde(flatten)]
+    repair: RepairReport,
+}
+
+impl VerifyOutput {
+    /// Returns true if no files are missing or modified, or all of them were repaired.
+    fn is_ok(&self) -> bool {
+        self.report
+            .missing
+            .iter()
+            .chain(self.report.modified.iter())
+            .all(|path| self.repair.repaired.contains(path))
+    }
+}
+
+impl std::fmt::Display for VerifyOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let report = &self.report;
+        write!(
+            f,
+            "{} ok, {} missing, {} modified, {} extra",
+            report.ok,
+            report.missing.len(),
+            report.modified.len(),
+            report.extra.len()
+        )?;
+        for path in report.missing.iter() {
+            write!(f, "\nMissing: {}", path.display())?;
+        }
+        for path in report.modified.iter() {
+            write!(f, "\nModified: {}", path.display())?;
+        }
+        for path in report.extra.iter() {
+            write!(f, "\nExtra: {}", path.display())?;
+        }
+        for path in self.repair.repaired.iter() {
+            write!(f, "\nRepaired: {}", path.display())?;
+        }
+        for item in self.repair.quarantined.iter() {
+            write!(
+                f,
+                "\nQuarantined: {} ({})",
+                item.path.display(),
+                item.reason
+            )?;
+        }
+        if report.needs_repair()
+            && self.repair.repaired.is_empty()
+            && self.repair.quarantined.is_empty()
+        {
+            write!(
+                f,
+                "\nRun 'verify --repair' to copy the missing and modified levels again"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorOutput {
+    error: String,
+}
+
+/// Runs a headless command, returning the process exit code.
+pub fn run(options: &Options, command: &Command) -> i32 {
+    let mut exit_code = 0;
+    let result = match command {
+        Command::Sync(_) => run_sync(options).map(|output| print_output(&output, options.json)),
+        Command::Launch(launch_options) => {
+            run_launch(options, launch_options).map(|output| print_output(&output, options.json))
+        }
+        Command::ListLevels(_) => {
+            run_list_levels(options).map(|output| print_output(&output, options.json))
+        }
+        Command::CheckConfig(_) => {
+            run_check_config(options).map(|output| print_output(&output, options.json))
+        }
+        Command::Diagnostics(diagnostics_options) => run_diagnostics(options, diagnostics_options)
+            .map(|output| print_output(&output, options.json)),
+        Command::Preview(preview_options) => {
+            run_preview(preview_options).map(|output| print_output(&output, options.json))
+        }
+        Command::ImportPack(import_pack_options) => run_import_pack(options, import_pack_options)
+            .map(|output| print_output(&output, options.json)),
+        Command::ExportPack(export_pack_options) => run_export_pack(options, export_pack_options)
+            .map(|output| print_output(&output, options.json)),
+        Command::Verify(verify_options) => run_verify(options, verify_options).map(|output| {
+            if !output.is_ok() {
+                exit_code = VERIFY_BROKEN_EXIT_CODE;
+            }
+            print_output(&output, options.json)
+        }),
+    };
+
+    match result {
+        Ok(()) => exit_code,
+        Err(e) => {
+            if options.json {
+                print_json(&ErrorOutput {
+                    error: e.to_string(),
+                });
+            } else {
+                eprintln!("{}", e);
+            }
+            1
+        }
+    }
+}
+
+fn print_output<T: Serialize + Display>(output: &T, json: bool) {
+    if json {
+        print_json(output);
+    } else {
+        println!("{}", output);
+    }
+}
+
+fn print_json<T: Serialize>(output: &T) {
+    match serde_json::to_string(output) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to serialize output: {}", e),
+    }
+}
+
+/// Loads the config and starts logging to its data dir.
+fn load_config(options: &Options) -> Result<Config, Box<dyn StdError>> {
+    let config = crate::config::load_from_file(&options.config).map_err(|e| {
+        format!(
+            "Failed to load config '{}': {}",
+            options.config.display(),
+            e
+        )
+    })?;
+
+    if let Err(e) = crate::logger::init(config.get_data_dir(), &config.log) {
+        eprintln!("Failed to init logger: {}", e);
+    }
+
+    Ok(config)
+}
+
+fn run_sync(options: &Options) -> Result<SyncOutput, Box<dyn StdError>> {
+    let config = load_config(options)?;
+
+    let mut tokio_rt = TokioRuntime::new()?;
+    let (steam_client, steam_single_client) = steamworks::Client::init()?;
+
+    let sync_dir = config.get_workshop_sync_path().clone();
+    ensure_sync_dir(&sync_dir)?;
+
+    let steam_workshop_sync_state = spawn_steam_workshop_sync(
+        &tokio_rt,
+        &steam_client,
+        sync_dir,
+        config.get_data_dir().clone(),
+        &config.sync_lists,
+        &config.sync,
+        Arc::default(),
+    )?;
+
+    let synced = loop {
+        steam_single_client.run_callbacks();
+
+        match &*steam_workshop_sync_state.lock() {
+            SteamWorkshopSyncState::Starting | SteamWorkshopSyncState::InProgress { .. } => {}
+            SteamWorkshopSyncState::Done(synced) => break *synced,
+            SteamWorkshopSyncState::Failed(e) => return Err(format!("Sync Failed: {}", e).into()),
+            SteamWorkshopSyncState::Offline => return Err("Steam is unavailable".into()),
+        }
+
+        std::thread::sleep(SYNC_POLL_INTERVAL);
+    };
+
+    let manifest = tokio_rt.block_on(SyncManifest::load(&get_sync_manifest_path(
+        config.get_data_dir(),
+    )));
+    let files = manifest
+        .items
+        .into_iter()
+        .map(|item| SyncedFile {
+            published_file_id: item.published_file_id,
+            source: item.source,
+            path: item.path,
+        })
+        .collect();
+
+    Ok(SyncOutput { synced, files })
+}
+
+fn run_launch(
+    options: &Options,
+    launch_options: &LaunchOptions,
+) -> Result<LaunchOutput, Box<dyn StdError>> {
+    let config = load_config(options)?;
+
+    let path = match launch_options.profile {
+        LaunchProfile::Game => config.get_game_path(),
+        LaunchProfile::Levelbuilder => config.get_levelbuilder_path(),
+    };
+    crate::util::open_program(&*path.to_string_lossy());
+
+    Ok(LaunchOutput {
+        profile: launch_options.profile,
+        path: path.clone(),
+    })
+}
+
+fn run_list_levels(options: &Options) -> Result<ListLevelsOutput, Box<dyn StdError>> {
+    let config = load_config(options)?;
+
+    let mut levels = Vec::new();
+    for list in config.sync_lists.iter().copied() {
+        let list_dir = config.get_workshop_sync_path().join(list.as_str());
+        if !list_dir.is_dir() {
+            continue;
+        }
+
+        for entry in std::fs::read_dir(&list_dir)? {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().map_or(true, |ext| ext != "txt") {
+                continue;
+            }
+
+            let name = match path.file_stem() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => continue,
+            };
+
+            levels.push(LevelEntry { name, list, path });
+        }
+    }
+    levels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(ListLevelsOutput { levels })
+}
+
+fn run_check_config(options: &Options) -> Result<CheckConfigOutput, Box<dyn StdError>> {
+    let config = load_config(options)?;
+
+    Ok(CheckConfigOutput {
+        path: options.config.clone(),
+        version: config.version,
+        workshop_sync_path: config.get_workshop_sync_path().clone(),
+        game_path: config.get_game_path().clone(),
+        levelbuilder_path: config.get_levelbuilder_path().clone(),
+    })
+}
+
+fn run_diagnostics(
+    options: &Options,
+    diagnostics_options: &DiagnosticsOptions,
+) -> Result<DiagnosticsOutput, Box<dyn StdError>> {
+    let config = load_config(options)?;
+
+    let path = diagnostics_options
+        .output
+        .clone()
+        .unwrap_or_else(|| crate::diagnostics::get_default_bundle_path(config.get_data_dir()));
+
+    // There is no window in headless mode, so there is no gpu info.
+    crate::diagnostics::export_bundle(&path, &config, None, diagnostics_options.redact)?;
+
+    Ok(DiagnosticsOutput {
+        path,
+        redacted: diagnostics_options.redact,
+    })
+}
+
+fn run_preview(preview_options: &PreviewOptions) -> Result<PreviewOutput, Box<dyn StdError>> {
+    if preview_options.tile_size == 0 {
+        return Err("The tile size must be positive".into());
+    }
+    if preview_options.tile_size > MAX_PREVIEW_TILE_SIZE {
+        return Err(format!("The tile size must be at most {}", MAX_PREVIEW_TILE_SIZE).into());
+    }
+
+    // Previews of unplayable levels are still useful, so only parse the level.
+    let level = Level::load(&preview_options.level)?;
+    let preview = level.render_preview(preview_options.tile_size);
+    preview.save_with_format(&preview_options.output, image::ImageFormat::Png)?;
+
+    Ok(PreviewOutput {
+        path: preview_options.output.clone(),
+        width: preview.width(),
+        height: preview.height(),
+    })
+}
+
+fn run_import_pack(
+    options: &Options,
+    import_pack_options: &ImportPackOptions,
+) -> Result<ImportPackOutput, Box<dyn StdError>> {
+    let config = load_config(options)?;
+
+    let sync_dir = config.get_workshop_sync_path();
+    ensure_sync_dir(sync_dir)?;
+    let levels = import_pack(&import_pack_options.pack, sync_dir)?;
+
+    Ok(ImportPackOutput { levels })
+}
+
+fn run_export_pack(
+    options: &Options,
+    export_pack_options: &ExportPackOptions,
+) -> Result<ExportPackOutput, Box<dyn StdError>> {
+    let config = load_config(options)?;
+    let data_dir = config.get_data_dir();
+
+    let mut tokio_rt = TokioRuntime::new()?;
+    let manifest = tokio_rt.block_on(SyncManifest::load(&get_sync_manifest_path(data_dir)));
+    let workshop_cache = WorkshopCache::load(data_dir)?;
+    let sources = get_pack_sources(
+        &export_pack_options.levels,
+        &manifest,
+        &workshop_cache.items,
+    );
+
+    let path = export_pack_options
+        .output
+        .clone()
+        .unwrap_or_else(|| get_default_pack_path(data_dir));
+    let manifest = export_pack(&path, &sources)?;
+
+    Ok(ExportPackOutput {
+        path,
+        levels: manifest.levels.len(),
+    })
+}
+
+fn run_verify(
+    options: &Options,
+    verify_options: &VerifyOptions,
+) -> Result<VerifyOutput, Box<dyn StdError>> {
+    let config = load_config(options)?;
+    let manifest_path = get_sync_manifest_path(config.get_data_dir());
+
+    let mut tokio_rt = TokioRuntime::new()?;
+    let mut manifest = tokio_rt.block_on(SyncManifest::load(&manifest_path));
+    let report = tokio_rt.block_on(verify_sync_dir(config.get_workshop_sync_path(), &manifest))?;
+
+    let mut repair = RepairReport::default();
+    if verify_options.repair && report.needs_repair() {
+        let (steam_client, _steam_single_client) = steamworks::Client::init()?;
+        repair = tokio_rt.block_on(repair_sync_dir(
+            &steam_client,
+            config.get_workshop_sync_path(),
+            config.get_data_dir(),
+            &mut manifest,
+            &report,
+        ))?;
+        tokio_rt.block_on(manifest.save(&manifest_path))?;
+    }
+
+    Ok(VerifyOutput { report, repair })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    fn make_output(missing: &[&str], modified: &[&str], repaired: &[&str]) -> VerifyOutput {
+        VerifyOutput {
+            report: VerifyReport {
+                missing: to_paths(missing),
+                modified: to_paths(modified),
+                ..VerifyReport::default()
+            },
+            repair: RepairReport {
+                repaired: to_paths(repaired),
+                quarantined: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn verify_output_is_ok_when_repaired() {
+        assert!(make_output(&[], &[], &[]).is_ok());
+        assert!(make_output(&["a.txt"], &["b.txt"], &["b.txt", "a.txt"]).is_ok());
+    }
+
+    #[test]
+    fn verify_output_is_broken_when_not_repaired() {
+        assert!(!make_output(&["a.txt"], &[], &[]).is_ok());
+        assert!(!make_output(&["a.txt"], &["b.txt"], &["a.txt"]).is_ok());
+    }
+}
@@ -1,18 +1,137 @@
-use serde::Deserialize;
-use std::path::{
-    Path,
-    PathBuf,
+mod migration;
+
+pub use self::migration::{
+    MigrationError,
+    CURRENT_VERSION,
+};
+use crate::retry::RetryPolicy;
+use log::LevelFilter;
+use notify::{
+    DebouncedEvent,
+    RecommendedWatcher,
+    RecursiveMode,
+    Watcher,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    error::Error as StdError,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::mpsc::{
+        Receiver,
+        TryRecvError,
+    },
+    time::Duration,
 };
 
-#[derive(Debug, Deserialize)]
+/// The time to wait for a burst of file events to settle before reloading.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    TomlSerialize(toml::ser::Error),
+    Migration(MigrationError),
+
+    EmptyWorkshopSyncPath,
+    InvalidWorkshopSyncPath,
+    EmptyDataDir,
+    InvalidLogMaxFileSize,
+    InvalidLogMaxFiles,
+    InvalidSyncConcurrency,
+    InvalidRetryAttempts,
+    InvalidResyncInterval,
+    EmptyGamePath,
+    EmptyLevelbuilderPath,
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        Self::Toml(e)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(e: toml::ser::Error) -> Self {
+        Self::TomlSerialize(e)
+    }
+}
+
+impl From<MigrationError> for ConfigError {
+    fn from(e: MigrationError) -> Self {
+        Self::Migration(e)
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => e.fmt(f),
+            Self::Toml(e) => e.fmt(f),
+            Self::TomlSerialize(e) => e.fmt(f),
+            Self::Migration(e) => e.fmt(f),
+
+            Self::EmptyWorkshopSyncPath => write!(f, "The workshop sync path is empty"),
+            Self::InvalidWorkshopSyncPath => {
+                write!(f, "The workshop sync path exists but is not a directory")
+            }
+            Self::EmptyDataDir => write!(f, "The data dir is empty"),
+            Self::InvalidLogMaxFileSize => write!(f, "The max log file size must be positive"),
+            Self::InvalidLogMaxFiles => write!(f, "The max number of log files must be positive"),
+            Self::InvalidSyncConcurrency => {
+                write!(f, "The number of items to sync at once must be positive")
+            }
+            Self::InvalidRetryAttempts => {
+                write!(f, "The max number of sync attempts must be positive")
+            }
+            Self::InvalidResyncInterval => write!(f, "The resync interval must be positive"),
+            Self::EmptyGamePath => write!(f, "The game path is empty"),
+            Self::EmptyLevelbuilderPath => write!(f, "The levelbuilder path is empty"),
+        }
+    }
+}
+
+impl StdError for ConfigError {}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
+    pub version: u32,
+
     pub workshop_sync_path: PathBuf,
 
-    #[serde(alias = "Levelbuilder")]
-    pub levelbuilder: LaunchConfig,
+    #[serde(default = "default_data_dir")]
+    pub data_dir: PathBuf,
+
+    /// Load workshop preview images from this dir instead of downloading them.
+    /// Images are looked up by the file name at the end of their url. Useful for testing.
+    #[serde(default)]
+    pub preview_source_dir: Option<PathBuf>,
 
-    #[serde(alias = "Game")]
+    /// The user lists to sync, each to its own subfolder of the workshop sync path.
+    /// Items in more than one list are only synced to the folder of the first one.
+    #[serde(default = "default_sync_lists")]
+    pub sync_lists: Vec<SyncList>,
+
+    pub levelbuilder: LaunchConfig,
     pub game: LaunchConfig,
+
+    #[serde(default)]
+    pub log: LogConfig,
+
+    #[serde(default)]
+    pub sync: SyncConfig,
 }
 
 impl Config {
@@ -20,6 +139,11 @@ impl Config {
         &self.workshop_sync_path
     }
 
+    /// Gets the dir the launcher stores its own files in, like logs.
+    pub fn get_data_dir(&self) -> &PathBuf {
+        &self.data_dir
+    }
+
     pub fn get_game_path(&self) -> &PathBuf {
         &self.game.path
     }
@@ -27,17 +151,345 @@ impl Config {
     pub fn get_levelbuilder_path(&self) -> &PathBuf {
         &self.levelbuilder.path
     }
+
+    /// Checks that the config values are usable.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.workshop_sync_path.as_os_str().is_empty() {
+            return Err(ConfigError::EmptyWorkshopSyncPath);
+        }
+
+        if self.workshop_sync_path.exists() && !self.workshop_sync_path.is_dir() {
+            return Err(ConfigError::InvalidWorkshopSyncPath);
+        }
+
+        if self.data_dir.as_os_str().is_empty() {
+            return Err(ConfigError::EmptyDataDir);
+        }
+
+        if self.log.max_file_size == 0 {
+            return Err(ConfigError::InvalidLogMaxFileSize);
+        }
+
+        if self.log.max_files == 0 {
+            return Err(ConfigError::InvalidLogMaxFiles);
+        }
+
+        if self.sync.concurrency == 0 {
+            return Err(ConfigError::InvalidSyncConcurrency);
+        }
+
+        if self.sync.retry.max_attempts == 0 {
+            return Err(ConfigError::InvalidRetryAttempts);
+        }
+
+        if self.sync.resync_interval_mins == Some(0) {
+            return Err(ConfigError::InvalidResyncInterval);
+        }
+
+        if self.game.path.as_os_str().is_empty() {
+            return Err(ConfigError::EmptyGamePath);
+        }
+
+        if self.levelbuilder.path.as_os_str().is_empty() {
+            return Err(ConfigError::EmptyLevelbuilderPath);
+        }
+
+        Ok(())
+    }
+}
+
+/// A list of workshop items related to the user that can be synced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncList {
+    Subscribed,
+    Published,
+    Favorited,
+    VotedOn,
+}
+
+impl SyncList {
+    /// Gets the name of this list, which is also the name of its subfolder in the workshop sync path.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Subscribed => "subscribed",
+            Self::Published => "published",
+            Self::Favorited => "favorited",
+            Self::VotedOn => "voted_on",
+        }
+    }
+
+    /// Gets the steam user list this list is queried with.
+    pub fn user_list(self) -> steamworks::UserList {
+        match self {
+            Self::Subscribed => steamworks::UserList::Subscribed,
+            Self::Published => steamworks::UserList::Published,
+            Self::Favorited => steamworks::UserList::Favorited,
+            Self::VotedOn => steamworks::UserList::VotedOn,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct LaunchConfig {
     pub path: PathBuf,
+
+    /// The log file written by the program, included in diagnostics bundles.
+    #[serde(default)]
+    pub log_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// The max level of messages to log.
+    pub level: LevelFilter,
+
+    /// The size in bytes a log file can grow to before it is rotated.
+    pub max_file_size: u64,
+
+    /// The number of log files to keep, including the current one.
+    pub max_files: u32,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            max_file_size: 1024 * 1024,
+            max_files: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// The max number of workshop items to copy at once.
+    pub concurrency: usize,
+
+    /// How often to sync again while the launcher is open, in minutes. None only syncs on start and when steam installs items.
+    pub resync_interval_mins: Option<u64>,
+
+    /// How to retry workshop queries and item copies that fail with a transient error.
+    /// This is a table, so it must come after the plain values for the config to serialize.
+    pub retry: RetryPolicy,
+}
+
+impl SyncConfig {
+    /// Gets how often to sync again while the launcher is open, if it should.
+    pub fn get_resync_interval(&self) -> Option<Duration> {
+        self.resync_interval_mins
+            .map(|mins| Duration::from_secs(mins.saturating_mul(60)))
+    }
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            resync_interval_mins: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+fn default_data_dir() -> PathBuf {
+    PathBuf::from("./data")
 }
 
-pub fn load_from_file<T: AsRef<Path>>(path: T) -> Result<Config, std::io::Error> {
+fn default_sync_lists() -> Vec<SyncList> {
+    vec![SyncList::Subscribed]
+}
+
+/// Loads and validates a config file.
+///
+/// Configs from older versions are migrated to the current version.
+/// The original file is kept next to it as a backup before the migrated config is written back.
+pub fn load_from_file<T: AsRef<Path>>(path: T) -> Result<Config, ConfigError> {
     let path = path.as_ref();
     let data = std::fs::read_to_string(path)?;
-    let config = toml::from_str(&data)?;
+
+    let mut table: toml::value::Table = toml::from_str(&data)?;
+    let old_version = self::migration::migrate(&mut table)?;
+    let table = toml::Value::Table(table);
+
+    if old_version != CURRENT_VERSION {
+        let mut backup_path = path.as_os_str().to_os_string();
+        backup_path.push(format!(".v{}.bak", old_version));
+        std::fs::write(&backup_path, &data)?;
+
+        std::fs::write(path, toml::to_string(&table)?)?;
+    }
+
+    let config: Config = table.try_into()?;
+    config.validate()?;
 
     Ok(config)
 }
+
+/// Watches a config file and reloads it when it changes.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    file_name: Option<std::ffi::OsString>,
+
+    rx: Receiver<DebouncedEvent>,
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Starts watching the config file at the given path.
+    pub fn new<T: Into<PathBuf>>(path: T) -> Result<Self, notify::Error> {
+        let path = path.into();
+        let file_name = path.file_name().map(|name| name.to_os_string());
+
+        // Editors often replace the file instead of writing to it,
+        // so watch the parent dir and filter by file name.
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = notify::watcher(tx, WATCH_DEBOUNCE)?;
+        watcher.watch(&parent, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            path,
+            file_name,
+
+            rx,
+            _watcher: watcher,
+        })
+    }
+
+    /// Checks for changes without blocking.
+    /// Returns the result of reloading the config if the file changed since the last poll.
+    pub fn poll(&self) -> Option<Result<Config, ConfigError>> {
+        let mut changed = false;
+        loop {
+            match self.rx.try_recv() {
+                Ok(event) => changed |= self.is_config_event(&event),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+            }
+        }
+
+        if changed {
+            Some(load_from_file(&self.path))
+        } else {
+            None
+        }
+    }
+
+    fn is_config_event(&self, event: &DebouncedEvent) -> bool {
+        let path = match event {
+            DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => path,
+            DebouncedEvent::Rename(_, to) => to,
+            _ => return false,
+        };
+
+        path.file_name() == self.file_name.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        retry::RetryableError,
+        util::get_test_dir,
+    };
+
+    #[test]
+    fn load_from_file_backs_up_old_version() {
+        let dir = get_test_dir("config-backup");
+        let path = dir.join("config.toml");
+        let data = format!(
+            r#"
+workshop_sync_path = {:?}
+
+[Levelbuilder]
+path = "levelbuilder.exe"
+
+[Game]
+path = "game.exe"
+"#,
+            dir.join("workshop")
+        );
+        std::fs::write(&path, &data).expect("failed to write config");
+
+        let config = load_from_file(&path).expect("failed to load config");
+        assert_eq!(config.version, CURRENT_VERSION);
+        assert_eq!(config.game.path, PathBuf::from("game.exe"));
+
+        let backup =
+            std::fs::read_to_string(dir.join("config.toml.v0.bak")).expect("failed to read backup");
+        assert_eq!(backup, data);
+
+        // The migrated config is written back, so loading it again does not make another backup.
+        std::fs::remove_file(dir.join("config.toml.v0.bak")).expect("failed to remove backup");
+        load_from_file(&path).expect("failed to load migrated config");
+        assert!(!dir.join("config.toml.v0.bak").exists());
+
+        std::fs::remove_dir_all(&dir).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        let config = Config {
+            version: CURRENT_VERSION,
+            workshop_sync_path: PathBuf::from("workshop"),
+            data_dir: PathBuf::from("data"),
+            preview_source_dir: Some(PathBuf::from("previews")),
+            sync_lists: vec![SyncList::Subscribed, SyncList::Favorited],
+            levelbuilder: LaunchConfig {
+                path: PathBuf::from("levelbuilder.exe"),
+                log_path: Some(PathBuf::from("levelbuilder.log")),
+            },
+            game: LaunchConfig {
+                path: PathBuf::from("game.exe"),
+                log_path: Some(PathBuf::from("game.log")),
+            },
+            log: LogConfig {
+                level: LevelFilter::Debug,
+                max_file_size: 4096,
+                max_files: 5,
+            },
+            sync: SyncConfig {
+                concurrency: 2,
+                resync_interval_mins: Some(15),
+                retry: RetryPolicy {
+                    max_attempts: 5,
+                    initial_delay_ms: 500,
+                    max_delay_ms: 10 * 1000,
+                    retryable: vec![RetryableError::Busy, RetryableError::Timeout],
+                },
+            },
+        };
+
+        let data = toml::to_string(&config).expect("failed to serialize config");
+        let parsed: Config = toml::from_str(&data).expect("failed to parse config");
+        assert_eq!(
+            toml::to_string(&parsed).expect("failed to serialize parsed config"),
+            data
+        );
+
+        assert_eq!(parsed.preview_source_dir, config.preview_source_dir);
+        assert_eq!(parsed.levelbuilder.log_path, config.levelbuilder.log_path);
+        assert_eq!(parsed.game.log_path, config.game.log_path);
+        assert_eq!(parsed.sync.resync_interval_mins, Some(15));
+        assert_eq!(parsed.sync.retry.retryable, config.sync.retry.retryable);
+    }
+
+    #[test]
+    fn resync_interval_saturates() {
+        let sync_config = SyncConfig {
+            resync_interval_mins: Some(u64::MAX),
+            ..SyncConfig::default()
+        };
+        assert_eq!(
+            sync_config.get_resync_interval(),
+            Some(Duration::from_secs(u64::MAX))
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use toml::{
+    value::Table,
+    Value,
+};
+
+/// The config version this launcher reads and writes.
+pub const CURRENT_VERSION: u32 = 1;
+
+/// A config migration step. It upgrades a config table from version `n` to `n + 1`.
+type Migration = fn(&mut Table) -> Result<(), MigrationError>;
+
+/// Migration steps, indexed by the version they upgrade from.
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [migrate_v0_to_v1];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    InvalidVersion,
+    UnsupportedVersion(u32),
+
+    NotATable(&'static str),
+    ConflictingKeys(&'static str, &'static str),
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidVersion => write!(f, "The config version is not a positive integer"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "The config version {} is newer than the supported version {}",
+                version, CURRENT_VERSION
+            ),
+
+            Self::NotATable(key) => write!(f, "'{}' is not a table", key),
+            Self::ConflictingKeys(old, new) => {
+                write!(f, "Both '{}' and '{}' are present", old, new)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// Gets the version of a config table. Configs from before versioning have no version and are version 0.
+pub fn get_version(table: &Table) -> Result<u32, MigrationError> {
+    match table.get("version") {
+        Some(Value::Integer(version)) if *version >= 0 && *version <= i64::from(u32::MAX) => {
+            Ok(*version as u32)
+        }
+        Some(_) => Err(MigrationError::InvalidVersion),
+        None => Ok(0),
+    }
+}
+
+/// Upgrades a config table to the current version.
+/// Returns the version it was upgraded from.
+pub fn migrate(table: &mut Table) -> Result<u32, MigrationError> {
+    let version = get_version(table)?;
+    if version > CURRENT_VERSION {
+        return Err(MigrationError::UnsupportedVersion(version));
+    }
+
+    for migration in MIGRATIONS[version as usize..].iter() {
+        migration(table)?;
+    }
+
+    table.insert("version".into(), Value::Integer(CURRENT_VERSION.into()));
+
+    Ok(version)
+}
+
+/// Version 0 used capitalized `Levelbuilder` and `Game` tables.
+/// Version 1 lowercases them and adds the `version` key.
+fn migrate_v0_to_v1(table: &mut Table) -> Result<(), MigrationError> {
+    rename_table(table, "Levelbuilder", "levelbuilder")?;
+    rename_table(table, "Game", "game")?;
+    table.insert("version".into(), Value::Integer(1));
+
+    Ok(())
+}
+
+/// Renames a table key, if present.
+fn rename_table(
+    table: &mut Table,
+    old: &'static str,
+    new: &'static str,
+) -> Result<(), MigrationError> {
+    let value = match table.remove(old) {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+
+    if !value.is_table() {
+        return Err(MigrationError::NotATable(old));
+    }
+
+    if table.contains_key(new) {
+        return Err(MigrationError::ConflictingKeys(old, new));
+    }
+
+    table.insert(new.into(), value);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Table {
+        toml::from_str(text).expect("invalid toml")
+    }
+
+    #[test]
+    fn migrate_v0_to_v1_renames_tables() {
+        let mut table = parse(
+            r#"
+            workshop_sync_path = "./workshop"
+
+            [Levelbuilder]
+            path = "levelbuilder.exe"
+
+            [Game]
+            path = "game.exe"
+            "#,
+        );
+
+        assert_eq!(migrate(&mut table).expect("failed to migrate"), 0);
+        assert_eq!(
+            get_version(&table).expect("invalid version"),
+            CURRENT_VERSION
+        );
+        assert!(!table.contains_key("Levelbuilder"));
+        assert!(!table.contains_key("Game"));
+        assert_eq!(
+            table["levelbuilder"]["path"].as_str(),
+            Some("levelbuilder.exe")
+        );
+        assert_eq!(table["game"]["path"].as_str(), Some("game.exe"));
+    }
+
+    #[test]
+    fn migrate_current_version_is_unchanged() {
+        let text = r#"
+            version = 1
+
+            [levelbuilder]
+            path = "levelbuilder.exe"
+        "#;
+        let mut table = parse(text);
+
+        assert_eq!(migrate(&mut table).expect("failed to migrate"), 1);
+        assert_eq!(table, parse(text));
+    }
+
+    #[test]
+    fn rename_table_conflicting_keys() {
+        let mut table = parse(
+            r#"
+            [Game]
+            path = "old.exe"
+
+            [game]
+            path = "new.exe"
+            "#,
+        );
+
+        assert!(matches!(
+            migrate(&mut table),
+            Err(MigrationError::ConflictingKeys("Game", "game"))
+        ));
+    }
+
+    #[test]
+    fn rename_table_not_a_table() {
+        let mut table = parse(r#"Levelbuilder = "levelbuilder.exe""#);
+
+        assert!(matches!(
+            migrate(&mut table),
+            Err(MigrationError::NotATable("Levelbuilder"))
+        ));
+    }
+
+    #[test]
+    fn missing_version_is_v0() {
+        assert_eq!(get_version(&Table::new()).expect("invalid version"), 0);
+    }
+
+    #[test]
+    fn invalid_version() {
+        for text in [
+            "version = -1",
+            "version = \"1\"",
+            "version = 1.5",
+            "version = 4294967296",
+        ]
+        .iter()
+        {
+            let mut table = parse(text);
+            assert!(
+                matches!(migrate(&mut table), Err(MigrationError::InvalidVersion)),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut table = parse(&format!("version = {}", CURRENT_VERSION + 1));
+
+        assert!(matches!(
+            migrate(&mut table),
+            Err(MigrationError::UnsupportedVersion(version)) if version == CURRENT_VERSION + 1
+        ));
+    }
+}
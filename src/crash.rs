@@ -0,0 +1,226 @@
+use crate::{
+    config::Config,
+    sync::SteamWorkshopSyncState,
+};
+use parking_lot::{
+    const_mutex,
+    Mutex,
+};
+use std::{
+    fmt::{
+        Display,
+        Write as _,
+    },
+    fs::File,
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
+};
+use zip::{
+    result::ZipError,
+    write::FileOptions,
+    ZipWriter,
+};
+
+/// The number of log lines to include in a crash report.
+const CRASH_REPORT_LOG_LINES: usize = 100;
+
+/// The name of the file that holds the name of a crash report the user has not seen yet.
+const PENDING_FILE_NAME: &str = "pending";
+
+/// Launcher state to include in crash reports. It is updated as the launcher runs.
+struct CrashContext {
+    data_dir: PathBuf,
+    config: String,
+    steam_workshop_sync_state: Option<Arc<Mutex<SteamWorkshopSyncState>>>,
+}
+
+static CRASH_CONTEXT: Mutex<Option<CrashContext>> = const_mutex(None);
+
+/// Gets the dir crash reports are written to.
+pub fn get_crash_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("crashes")
+}
+
+/// Installs a panic hook that writes a crash report to the data dir of the given config.
+pub fn install_panic_hook(config: &Config) {
+    set_config(config);
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        // The panic may have happened while the logger was locked, so it is not used here.
+        let data_dir = get_data_dir();
+        let log_error = |message: &dyn Display| {
+            crate::logger::write_error_unlocked(data_dir.as_deref(), module_path!(), message)
+        };
+
+        // This is logged first so it is in the last log lines of the crash report.
+        log_error(&format_args!("Panic: {}", info));
+        match write_crash_report(info) {
+            Ok(Some(path)) => {
+                log_error(&format_args!("Wrote crash report to '{}'", path.display()))
+            }
+            Ok(None) => {}
+            Err(e) => log_error(&format_args!("Failed to write crash report: {}", e)),
+        }
+
+        default_hook(info);
+    }));
+}
+
+/// Gets the data dir crash reports are written to, or None if the panic hook is not installed
+/// or the panic happened while the crash context was locked.
+fn get_data_dir() -> Option<PathBuf> {
+    CRASH_CONTEXT
+        .try_lock()?
+        .as_ref()
+        .map(|crash_context| crash_context.data_dir.clone())
+}
+
+/// Updates the config included in crash reports.
+pub fn set_config(config: &Config) {
+    let config_string =
+        toml::to_string(config).unwrap_or_else(|e| format!("Failed to serialize config: {}", e));
+
+    let mut crash_context = CRASH_CONTEXT.lock();
+    match crash_context.as_mut() {
+        Some(crash_context) => {
+            crash_context.data_dir = config.get_data_dir().clone();
+            crash_context.config = config_string;
+        }
+        None => {
+            *crash_context = Some(CrashContext {
+                data_dir: config.get_data_dir().clone(),
+                config: config_string,
+                steam_workshop_sync_state: None,
+            });
+        }
+    }
+}
+
+/// Updates the sync state included in crash reports.
+pub fn set_steam_workshop_sync_state(
+    steam_workshop_sync_state: Arc<Mutex<SteamWorkshopSyncState>>,
+) {
+    if let Some(crash_context) = CRASH_CONTEXT.lock().as_mut() {
+        crash_context.steam_workshop_sync_state = Some(steam_workshop_sync_state);
+    }
+}
+
+/// Writes a crash report for a panic, given the panic info.
+/// Returns the path of the report, or None if the panic hook is not installed.
+fn write_crash_report(info: &dyn Display) -> std::io::Result<Option<PathBuf>> {
+    // Don't block if the panic happened while the context was locked.
+    let crash_context = match CRASH_CONTEXT.try_lock() {
+        Some(crash_context) => crash_context,
+        None => return Ok(None),
+    };
+    let crash_context = match crash_context.as_ref() {
+        Some(crash_context) => crash_context,
+        None => return Ok(None),
+    };
+
+    let steam_workshop_sync_state = match crash_context.steam_workshop_sync_state.as_ref() {
+        Some(state) => match state.try_lock() {
+            Some(state) => format!("{:?}", *state),
+            None => "Locked".into(),
+        },
+        None => "Unknown".into(),
+    };
+
+    let log_path = crate::logger::get_log_path(&crash_context.data_dir);
+    let log_lines = match std::fs::read_to_string(&log_path) {
+        Ok(log) => {
+            let lines: Vec<&str> = log.lines().collect();
+            let start = lines.len().saturating_sub(CRASH_REPORT_LOG_LINES);
+            lines[start..].join("\n")
+        }
+        Err(e) => format!("Failed to read '{}': {}", log_path.display(), e),
+    };
+
+    let now = chrono::Local::now();
+    let mut report = String::new();
+    // Writing to a String cannot fail.
+    let _ = writeln!(report, "Skeleton Sprint Launcher Crash Report");
+    let _ = writeln!(report, "Version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "Time: {}", now.to_rfc3339());
+    let _ = writeln!(
+        report,
+        "OS: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    let _ = writeln!(report);
+    let _ = writeln!(report, "Panic:\n{}\n", info);
+    let _ = writeln!(report, "Backtrace:\n{:?}\n", backtrace::Backtrace::new());
+    let _ = writeln!(report, "Sync State:\n{}\n", steam_workshop_sync_state);
+    let _ = writeln!(report, "Config:\n{}\n", crash_context.config);
+    let _ = writeln!(report, "Last Log Lines:\n{}", log_lines);
+
+    let crash_dir = get_crash_dir(&crash_context.data_dir);
+    std::fs::create_dir_all(&crash_dir)?;
+
+    let file_name = format!("crash-{}.txt", now.format("%Y%m%d-%H%M%S"));
+    let path = crash_dir.join(&file_name);
+    std::fs::write(&path, report)?;
+    std::fs::write(crash_dir.join(PENDING_FILE_NAME), &file_name)?;
+
+    Ok(Some(path))
+}
+
+/// Gets the crash report from the last run, if the user has not seen it yet.
+pub fn get_pending_crash_report(data_dir: &Path) -> Option<PathBuf> {
+    let crash_dir = get_crash_dir(data_dir);
+    let file_name = std::fs::read_to_string(crash_dir.join(PENDING_FILE_NAME)).ok()?;
+    let path = crash_dir.join(file_name.trim());
+
+    if path.is_file() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Marks the pending crash report as seen.
+pub fn clear_pending_crash_report(data_dir: &Path) -> std::io::Result<()> {
+    let path = get_crash_dir(data_dir).join(PENDING_FILE_NAME);
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Zips a crash report together with the current log files.
+/// Returns the path of the zip file, which is next to the report.
+pub fn zip_crash_report(report_path: &Path, data_dir: &Path) -> Result<PathBuf, ZipError> {
+    let zip_path = report_path.with_extension("zip");
+    let mut zip = ZipWriter::new(File::create(&zip_path)?);
+    let options = FileOptions::default();
+
+    if let Some(file_name) = report_path.file_name() {
+        zip.start_file(file_name.to_string_lossy(), options)?;
+        zip.write_all(&std::fs::read(report_path)?)?;
+    }
+
+    let log_dir = crate::logger::get_log_dir(data_dir);
+    if log_dir.is_dir() {
+        for entry in std::fs::read_dir(&log_dir)? {
+            let path = entry?.path();
+            let file_name = match path.file_name() {
+                Some(file_name) if path.is_file() => file_name.to_string_lossy().into_owned(),
+                _ => continue,
+            };
+
+            zip.start_file(format!("logs/{}", file_name), options)?;
+            zip.write_all(&std::fs::read(&path)?)?;
+        }
+    }
+
+    zip.finish()?;
+
+    Ok(zip_path)
+}
@@ -0,0 +1,251 @@
+use crate::{
+    config::Config,
+    sync::get_sync_manifest_path,
+};
+use std::{
+    borrow::Cow,
+    fmt::Write as _,
+    fs::File,
+    io::{
+        Seek,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use zip::{
+    result::ZipError,
+    write::FileOptions,
+    ZipWriter,
+};
+
+/// The text user paths are replaced with when redacting.
+const REDACTED_HOME: &str = "<HOME>";
+const REDACTED_USER: &str = "<USER>";
+
+/// Info about the OpenGL context the launcher renders with.
+#[derive(Debug, Clone)]
+pub struct GpuInfo {
+    pub vendor: String,
+    pub renderer: String,
+    pub version: String,
+    pub shading_language_version: String,
+}
+
+/// Replaces the user's home dir and name in text so bundles can be shared.
+struct Redactor {
+    replacements: Vec<(String, String)>,
+}
+
+impl Redactor {
+    /// Makes a redactor for the current user. If enabled is false, nothing is redacted.
+    fn new(enabled: bool) -> Self {
+        let mut replacements = Vec::new();
+
+        if enabled {
+            for var in ["USERPROFILE", "HOME"].iter() {
+                if let Some(home) = std::env::var_os(var) {
+                    let home = home.to_string_lossy().into_owned();
+                    if !home.is_empty() {
+                        // Configs often use forward slashes on windows.
+                        replacements.push((home.replace('\\', "/"), REDACTED_HOME.into()));
+                        replacements.push((home, REDACTED_HOME.into()));
+                    }
+                }
+            }
+
+            for var in ["USERNAME", "USER"].iter() {
+                if let Some(user) = std::env::var_os(var) {
+                    let user = user.to_string_lossy().into_owned();
+                    if !user.is_empty() {
+                        // Only replace the user name as a path component, as short names are common words.
+                        replacements
+                            .push((format!("\\{}\\", user), format!("\\{}\\", REDACTED_USER)));
+                        replacements.push((format!("/{}/", user), format!("/{}/", REDACTED_USER)));
+                    }
+                }
+            }
+        }
+
+        // Replace longer matches first so the home dir is replaced before the user name in it.
+        replacements.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        replacements.dedup();
+
+        Self { replacements }
+    }
+
+    fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for (from, to) in self.replacements.iter() {
+            if text.contains(from.as_str()) {
+                text = Cow::Owned(text.replace(from.as_str(), to.as_str()));
+            }
+        }
+        text
+    }
+}
+
+/// Gets the dir diagnostics bundles are written to by default.
+pub fn get_diagnostics_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("diagnostics")
+}
+
+/// Makes a path for a new diagnostics bundle in the diagnostics dir.
+pub fn get_default_bundle_path(data_dir: &Path) -> PathBuf {
+    let now = chrono::Local::now();
+    get_diagnostics_dir(data_dir).join(format!("diagnostics-{}.zip", now.format("%Y%m%d-%H%M%S")))
+}
+
+/// Writes a zip of everything useful for debugging the launcher to the given path.
+///
+/// This includes the config, sync manifest, launcher and game logs, system info, and a listing of the sync dir.
+/// If redact_paths is true, the user's home dir and name are removed from text files.
+pub fn export_bundle(
+    path: &Path,
+    config: &Config,
+    gpu_info: Option<&GpuInfo>,
+    redact_paths: bool,
+) -> Result<(), ZipError> {
+    let redactor = Redactor::new(redact_paths);
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut zip = ZipWriter::new(File::create(path)?);
+
+    let config_string =
+        toml::to_string(config).unwrap_or_else(|e| format!("Failed to serialize config: {}", e));
+    add_text(&mut zip, &redactor, "config.toml", &config_string)?;
+
+    add_text(
+        &mut zip,
+        &redactor,
+        "system.txt",
+        &get_system_info(gpu_info),
+    )?;
+
+    add_text(
+        &mut zip,
+        &redactor,
+        "workshop_sync_listing.txt",
+        &get_dir_listing(config.get_workshop_sync_path()),
+    )?;
+
+    let manifest_path = get_sync_manifest_path(config.get_data_dir());
+    add_text_file(&mut zip, &redactor, "sync_manifest.json", &manifest_path)?;
+
+    let log_dir = crate::logger::get_log_dir(config.get_data_dir());
+    if log_dir.is_dir() {
+        for entry in std::fs::read_dir(&log_dir)? {
+            let path = entry?.path();
+            if let Some(file_name) = path.file_name().filter(|_| path.is_file()) {
+                let name = format!("logs/{}", file_name.to_string_lossy());
+                add_text_file(&mut zip, &redactor, &name, &path)?;
+            }
+        }
+    }
+
+    let game_logs = [
+        ("game", config.game.log_path.as_ref()),
+        ("levelbuilder", config.levelbuilder.log_path.as_ref()),
+    ];
+    for (name, log_path) in game_logs.iter() {
+        if let Some(log_path) = log_path {
+            let name = format!("game_logs/{}.log", name);
+            add_text_file(&mut zip, &redactor, &name, log_path)?;
+        }
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+/// Adds a text file to the zip, redacting it.
+fn add_text<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    redactor: &Redactor,
+    name: &str,
+    text: &str,
+) -> Result<(), ZipError> {
+    zip.start_file(name, FileOptions::default())?;
+    zip.write_all(redactor.redact(text).as_bytes())?;
+    Ok(())
+}
+
+/// Adds a file from disk to the zip, redacting it.
+/// If the file cannot be read, the error is written instead, as a missing file is useful to know about.
+fn add_text_file<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    redactor: &Redactor,
+    name: &str,
+    path: &Path,
+) -> Result<(), ZipError> {
+    let text = match std::fs::read(path) {
+        Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+        Err(e) => format!("Failed to read '{}': {}", path.display(), e),
+    };
+    add_text(zip, redactor, name, &text)
+}
+
+fn get_system_info(gpu_info: Option<&GpuInfo>) -> String {
+    let mut info = String::new();
+    // Writing to a String cannot fail.
+    let _ = writeln!(info, "Launcher Version: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(info, "OS: {}", std::env::consts::OS);
+    let _ = writeln!(info, "OS Family: {}", std::env::consts::FAMILY);
+    let _ = writeln!(info, "Arch: {}", std::env::consts::ARCH);
+
+    match gpu_info {
+        Some(gpu_info) => {
+            let _ = writeln!(info, "GPU Vendor: {}", gpu_info.vendor);
+            let _ = writeln!(info, "GPU Renderer: {}", gpu_info.renderer);
+            let _ = writeln!(info, "OpenGL Version: {}", gpu_info.version);
+            let _ = writeln!(info, "GLSL Version: {}", gpu_info.shading_language_version);
+        }
+        None => {
+            let _ = writeln!(info, "GPU: Unknown, no window was opened");
+        }
+    }
+
+    info
+}
+
+/// Lists every file under a dir with its size.
+fn get_dir_listing(dir: &Path) -> String {
+    let mut listing = String::new();
+    let mut stack = vec![dir.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                let _ = writeln!(listing, "{}: {}", dir.display(), e);
+                continue;
+            }
+        };
+
+        let mut entries: Vec<_> = entries.filter_map(|entry| entry.ok()).collect();
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => {
+                    let _ = writeln!(listing, "{}/", path.display());
+                    stack.push(path);
+                }
+                Ok(metadata) => {
+                    let _ = writeln!(listing, "{} ({} bytes)", path.display(), metadata.len());
+                }
+                Err(e) => {
+                    let _ = writeln!(listing, "{}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+
+    listing
+}
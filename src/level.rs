@@ -0,0 +1,425 @@
+use image::{
+    Rgba,
+    RgbaImage,
+};
+use std::{
+    error::Error as StdError,
+    path::Path,
+};
+
+/// The number of columns in a level.
+pub const LEVEL_WIDTH: usize = 32;
+
+/// The number of rows in a level.
+pub const LEVEL_HEIGHT: usize = 18;
+
+/// The default width and height in pixels of a tile in a rendered preview.
+pub const PREVIEW_TILE_SIZE: u32 = 6;
+
+/// The largest tile size a preview can be rendered with, which keeps the image under 10 megapixels.
+pub const MAX_PREVIEW_TILE_SIZE: u32 = 128;
+
+/// A block or entity in a level grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    Empty,
+    Background,
+    Block,
+    Dark,
+    Scaffold,
+    Wire,
+    Torch,
+    Note,
+    Key,
+    Lock,
+    Switch,
+    SwitchCeiling,
+    ToggleBlockSolid,
+    ToggleBlockPhased,
+    OneWayWallUp,
+    OneWayWallDown,
+    OneWayWallLeft,
+    OneWayWallRight,
+    PipeIn,
+    PipeOut,
+    PipePhase,
+    PipeSolid,
+    PowerUpBurrow,
+    PowerUpRecall,
+    Player,
+    Exit,
+    SecretExit,
+}
+
+impl Tile {
+    /// Every tile with its code in the level format.
+    const CODES: [(&'static str, Self); 27] = [
+        ("00", Self::Empty),
+        ("BG", Self::Background),
+        ("B0", Self::Block),
+        ("D0", Self::Dark),
+        ("SC", Self::Scaffold),
+        ("WI", Self::Wire),
+        ("TO", Self::Torch),
+        ("NO", Self::Note),
+        ("K0", Self::Key),
+        ("L0", Self::Lock),
+        ("S0", Self::Switch),
+        ("S1", Self::SwitchCeiling),
+        ("T1", Self::ToggleBlockSolid),
+        ("T0", Self::ToggleBlockPhased),
+        ("OU", Self::OneWayWallUp),
+        ("OD", Self::OneWayWallDown),
+        ("OL", Self::OneWayWallLeft),
+        ("OR", Self::OneWayWallRight),
+        ("PI", Self::PipeIn),
+        ("PO", Self::PipeOut),
+        ("PP", Self::PipePhase),
+        ("PS", Self::PipeSolid),
+        ("UB", Self::PowerUpBurrow),
+        ("UR", Self::PowerUpRecall),
+        ("P0", Self::Player),
+        ("E0", Self::Exit),
+        ("E1", Self::SecretExit),
+    ];
+
+    /// Gets the tile with the given code, ignoring case.
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::CODES
+            .iter()
+            .find(|(tile_code, _)| tile_code.eq_ignore_ascii_case(code))
+            .map(|(_, tile)| *tile)
+    }
+
+    /// Gets the color this tile is drawn with in previews.
+    pub fn color(self) -> Rgba<u8> {
+        let [r, g, b] = match self {
+            Self::Empty => [0, 0, 0],
+            Self::Background => [40, 40, 48],
+            Self::Block => [128, 128, 128],
+            Self::Dark => [64, 64, 64],
+            Self::Scaffold => [139, 90, 43],
+            Self::Wire => [200, 60, 60],
+            Self::Torch => [255, 170, 0],
+            Self::Note => [240, 230, 140],
+            Self::Key => [255, 215, 0],
+            Self::Lock => [184, 134, 11],
+            Self::Switch | Self::SwitchCeiling => [220, 20, 60],
+            Self::ToggleBlockSolid => [70, 130, 180],
+            Self::ToggleBlockPhased => [30, 60, 90],
+            Self::OneWayWallUp
+            | Self::OneWayWallDown
+            | Self::OneWayWallLeft
+            | Self::OneWayWallRight => [160, 160, 200],
+            Self::PipeIn | Self::PipeOut => [0, 160, 0],
+            Self::PipePhase => [0, 90, 0],
+            Self::PipeSolid => [0, 120, 0],
+            Self::PowerUpBurrow | Self::PowerUpRecall => [186, 85, 211],
+            Self::Player => [255, 255, 255],
+            Self::Exit => [0, 255, 255],
+            Self::SecretExit => [255, 0, 255],
+        };
+        Rgba([r, g, b, 255])
+    }
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(std::io::Error),
+
+    /// The file is not valid utf8.
+    NotText,
+
+    /// The level does not have LEVEL_HEIGHT rows. Holds the number of rows.
+    InvalidHeight(usize),
+
+    /// A row does not have LEVEL_WIDTH columns.
+    InvalidWidth {
+        row: usize,
+        width: usize,
+    },
+
+    /// A tile code is not known.
+    UnknownTile {
+        row: usize,
+        column: usize,
+        code: String,
+    },
+
+    MissingSpawn,
+
+    /// There is more than one player. Holds the number of players.
+    MultipleSpawns(usize),
+
+    MissingExit,
+}
+
+impl std::fmt::Display for LevelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => e.fmt(f),
+
+            Self::NotText => write!(f, "The level is not text"),
+            Self::InvalidHeight(height) => write!(
+                f,
+                "The level has {} rows, but it should have {}",
+                height, LEVEL_HEIGHT
+            ),
+            Self::InvalidWidth { row, width } => write!(
+                f,
+                "Row {} has {} tiles, but it should have {}",
+                row + 1,
+                width,
+                LEVEL_WIDTH
+            ),
+            Self::UnknownTile { row, column, code } => write!(
+                f,
+                "Unknown tile '{}' at row {}, column {}",
+                code,
+                row + 1,
+                column + 1
+            ),
+            Self::MissingSpawn => write!(f, "The level has no player spawn"),
+            Self::MultipleSpawns(n) => {
+                write!(f, "The level has {} player spawns, but should have 1", n)
+            }
+            Self::MissingExit => write!(f, "The level has no exit"),
+        }
+    }
+}
+
+impl From<std::io::Error> for LevelError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl StdError for LevelError {}
+
+/// A Skeleton Sprint level, a LEVEL_WIDTH by LEVEL_HEIGHT grid of tiles.
+#[derive(Debug, Clone)]
+pub struct Level {
+    /// The tiles in row-major order, starting at the top left.
+    tiles: Vec<Tile>,
+}
+
+impl Level {
+    /// Parses a level from its text format, without checking that it is playable.
+    ///
+    /// The format is LEVEL_HEIGHT lines of LEVEL_WIDTH comma-separated tile codes.
+    /// Whitespace around codes, a trailing comma on a line and blank lines are ignored.
+    pub fn parse(text: &str) -> Result<Self, LevelError> {
+        let rows: Vec<&str> = text
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect();
+        if rows.len() != LEVEL_HEIGHT {
+            return Err(LevelError::InvalidHeight(rows.len()));
+        }
+
+        let mut tiles = Vec::with_capacity(LEVEL_WIDTH * LEVEL_HEIGHT);
+        for (row, line) in rows.iter().enumerate() {
+            let line = line.strip_suffix(',').unwrap_or(line);
+            let codes: Vec<&str> = line.split(',').map(|code| code.trim()).collect();
+            if codes.len() != LEVEL_WIDTH {
+                return Err(LevelError::InvalidWidth {
+                    row,
+                    width: codes.len(),
+                });
+            }
+
+            for (column, code) in codes.iter().enumerate() {
+                let tile = Tile::from_code(code).ok_or_else(|| LevelError::UnknownTile {
+                    row,
+                    column,
+                    code: (*code).into(),
+                })?;
+                tiles.push(tile);
+            }
+        }
+
+        Ok(Self { tiles })
+    }
+
+    /// Parses a level from the data of a level file.
+    pub fn parse_bytes(data: &[u8]) -> Result<Self, LevelError> {
+        let text = std::str::from_utf8(data).map_err(|_| LevelError::NotText)?;
+        Self::parse(text)
+    }
+
+    /// Reads and parses a level file.
+    pub fn load(path: &Path) -> Result<Self, LevelError> {
+        Self::parse_bytes(&std::fs::read(path)?)
+    }
+
+    /// Gets the tile at the given column and row.
+    pub fn get(&self, x: usize, y: usize) -> Tile {
+        self.tiles[(y * LEVEL_WIDTH) + x]
+    }
+
+    /// Renders a miniature of the level, drawing each tile as a square of tile_size pixels.
+    /// The tile size is clamped to MAX_PREVIEW_TILE_SIZE.
+    pub fn render_preview(&self, tile_size: u32) -> RgbaImage {
+        let tile_size = tile_size.min(MAX_PREVIEW_TILE_SIZE);
+        let width = LEVEL_WIDTH as u32 * tile_size;
+        let height = LEVEL_HEIGHT as u32 * tile_size;
+        RgbaImage::from_fn(width, height, |x, y| {
+            self.get((x / tile_size) as usize, (y / tile_size) as usize)
+                .color()
+        })
+    }
+
+    /// Checks that the level is playable, with exactly one player spawn and at least one exit.
+    pub fn validate(&self) -> Result<(), LevelError> {
+        let spawns = self.count(Tile::Player);
+        if spawns == 0 {
+            return Err(LevelError::MissingSpawn);
+        }
+        if spawns > 1 {
+            return Err(LevelError::MultipleSpawns(spawns));
+        }
+
+        if self.count(Tile::Exit) + self.count(Tile::SecretExit) == 0 {
+            return Err(LevelError::MissingExit);
+        }
+
+        Ok(())
+    }
+
+    /// Counts the tiles of the given kind.
+    fn count(&self, tile: Tile) -> usize {
+        self.tiles.iter().filter(|t| **t == tile).count()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Makes level text from rows of codes, filling the rest with empty tiles.
+    pub(crate) fn make_level(tiles: &[(usize, usize, &str)]) -> String {
+        let mut rows = vec![vec!["00"; LEVEL_WIDTH]; LEVEL_HEIGHT];
+        for (x, y, code) in tiles.iter() {
+            rows[*y][*x] = code;
+        }
+        rows.iter().map(|row| row.join(",") + "\n").collect()
+    }
+
+    #[test]
+    fn parse_valid_level() {
+        let text = make_level(&[(1, 16, "P0"), (30, 16, "E0"), (5, 17, "B0")]);
+        let level = Level::parse(&text).expect("failed to parse level");
+        level.validate().expect("level is invalid");
+
+        assert_eq!(level.get(1, 16), Tile::Player);
+        assert_eq!(level.get(30, 16), Tile::Exit);
+        assert_eq!(level.get(5, 17), Tile::Block);
+        assert_eq!(level.get(0, 0), Tile::Empty);
+    }
+
+    #[test]
+    fn parse_ignores_whitespace_and_trailing_commas() {
+        let text = make_level(&[(1, 16, "P0"), (30, 16, "E1")])
+            .lines()
+            .map(|line| format!("  {} ,  \n\n", line.replace(',', " , ")))
+            .collect::<String>();
+        let level = Level::parse(&text).expect("failed to parse level");
+        level.validate().expect("level is invalid");
+    }
+
+    #[test]
+    fn tile_codes() {
+        for (code, tile) in Tile::CODES.iter() {
+            assert_eq!(Tile::from_code(code), Some(*tile));
+            assert_eq!(Tile::from_code(&code.to_lowercase()), Some(*tile));
+        }
+        assert_eq!(Tile::from_code("ZZ"), None);
+
+        // Every tile has exactly one code.
+        for (i, (_, tile)) in Tile::CODES.iter().enumerate() {
+            assert!(
+                Tile::CODES[i + 1..].iter().all(|(_, other)| other != tile),
+                "{:?} has more than one code",
+                tile
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_height() {
+        let text = make_level(&[]);
+        let text: String = text
+            .lines()
+            .skip(1)
+            .map(|line| line.to_string() + "\n")
+            .collect();
+
+        assert!(matches!(
+            Level::parse(&text),
+            Err(LevelError::InvalidHeight(height)) if height == LEVEL_HEIGHT - 1
+        ));
+    }
+
+    #[test]
+    fn invalid_width() {
+        let text = make_level(&[]).replacen("00,", "", 1);
+
+        assert!(matches!(
+            Level::parse(&text),
+            Err(LevelError::InvalidWidth { row: 0, width }) if width == LEVEL_WIDTH - 1
+        ));
+    }
+
+    #[test]
+    fn unknown_tile() {
+        let text = make_level(&[(3, 2, "ZZ")]);
+
+        assert!(matches!(
+            Level::parse(&text),
+            Err(LevelError::UnknownTile { row: 2, column: 3, code }) if code == "ZZ"
+        ));
+    }
+
+    #[test]
+    fn not_text() {
+        assert!(matches!(
+            Level::parse_bytes(&[0xFF, 0xFE]),
+            Err(LevelError::NotText)
+        ));
+    }
+
+    #[test]
+    fn missing_spawn() {
+        let level = Level::parse(&make_level(&[(30, 16, "E0")])).expect("failed to parse level");
+        assert!(matches!(level.validate(), Err(LevelError::MissingSpawn)));
+    }
+
+    #[test]
+    fn multiple_spawns() {
+        let level = Level::parse(&make_level(&[(1, 16, "P0"), (2, 16, "P0"), (30, 16, "E0")]))
+            .expect("failed to parse level");
+        assert!(matches!(
+            level.validate(),
+            Err(LevelError::MultipleSpawns(2))
+        ));
+    }
+
+    #[test]
+    fn missing_exit() {
+        let level = Level::parse(&make_level(&[(1, 16, "P0")])).expect("failed to parse level");
+        assert!(matches!(level.validate(), Err(LevelError::MissingExit)));
+    }
+
+    #[test]
+    fn render_preview_size() {
+        let level = Level::parse(&make_level(&[])).expect("failed to parse level");
+        let image = level.render_preview(2);
+
+        assert_eq!(image.width(), LEVEL_WIDTH as u32 * 2);
+        assert_eq!(image.height(), LEVEL_HEIGHT as u32 * 2);
+
+        let image = level.render_preview(u32::MAX);
+        assert_eq!(image.width(), LEVEL_WIDTH as u32 * MAX_PREVIEW_TILE_SIZE);
+    }
+}
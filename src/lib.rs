@@ -0,0 +1,13 @@
+//! The parts of the launcher that don't need a window. The launcher binary and the benches use them.
+
+pub mod config;
+pub mod level;
+pub mod library;
+pub mod logger;
+pub mod pack;
+pub mod preview;
+pub mod retry;
+pub mod steamworks_util;
+pub mod sync;
+pub mod util;
+pub mod workshop;
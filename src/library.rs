@@ -0,0 +1,325 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    error::Error as StdError,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+#[derive(Debug)]
+pub enum LibraryError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+
+    EmptyPlaylistName,
+    DuplicatePlaylist(String),
+    EmptyPlaylist,
+}
+
+impl From<std::io::Error> for LibraryError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LibraryError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl std::fmt::Display for LibraryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => e.fmt(f),
+            Self::Json(e) => e.fmt(f),
+
+            Self::EmptyPlaylistName => write!(f, "The playlist name is empty"),
+            Self::DuplicatePlaylist(name) => {
+                write!(f, "A playlist named '{}' already exists", name)
+            }
+            Self::EmptyPlaylist => write!(f, "The playlist has no levels"),
+        }
+    }
+}
+
+impl StdError for LibraryError {}
+
+/// What the user noted about a level in the library.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LevelInfo {
+    pub favorite: bool,
+    pub tags: Vec<String>,
+    pub notes: String,
+}
+
+/// An ordered set of levels, for playtests.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Playlist {
+    pub name: String,
+    pub levels: Vec<PathBuf>,
+}
+
+impl Playlist {
+    /// Swaps the level at the index with the one before it.
+    pub fn move_up(&mut self, index: usize) {
+        if index > 0 && index < self.levels.len() {
+            self.levels.swap(index - 1, index);
+        }
+    }
+
+    /// Swaps the level at the index with the one after it.
+    pub fn move_down(&mut self, index: usize) {
+        if index + 1 < self.levels.len() {
+            self.levels.swap(index, index + 1);
+        }
+    }
+
+    /// Copies the levels into the target dir, prefixed with their position so they sort in order.
+    /// Returns the paths of the copies.
+    pub async fn export(&self, target_dir: &Path) -> Result<Vec<PathBuf>, LibraryError> {
+        if self.levels.is_empty() {
+            return Err(LibraryError::EmptyPlaylist);
+        }
+
+        tokio::fs::create_dir_all(target_dir).await?;
+
+        let mut paths = Vec::with_capacity(self.levels.len());
+        for (i, level_path) in self.levels.iter().enumerate() {
+            let file_name = level_path
+                .file_name()
+                .map_or(Cow::Borrowed(""), |file_name| file_name.to_string_lossy());
+            let path = target_dir.join(format!("{:03} - {}", i + 1, file_name));
+            tokio::fs::copy(level_path, &path).await?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+}
+
+/// The local level library, which keeps the user's favorites, tags, notes and playlists.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Library {
+    /// Folders with levels to show in addition to the sync dir.
+    #[serde(default)]
+    pub folders: Vec<PathBuf>,
+
+    /// Info for each level by path. Levels without any info are not stored.
+    #[serde(default)]
+    pub levels: BTreeMap<PathBuf, LevelInfo>,
+
+    #[serde(default)]
+    pub playlists: Vec<Playlist>,
+}
+
+impl Library {
+    /// Gets the path of the library in the data dir.
+    pub fn get_path(data_dir: &Path) -> PathBuf {
+        data_dir.join("library.json")
+    }
+
+    /// Loads the library, returning an empty one if it does not exist.
+    pub fn load(data_dir: &Path) -> Result<Self, LibraryError> {
+        match std::fs::read(Self::get_path(data_dir)) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Saves the library to the data dir.
+    pub async fn save(&self, data_dir: &Path) -> Result<(), LibraryError> {
+        let data = serde_json::to_vec_pretty(self)?;
+        tokio::fs::create_dir_all(data_dir).await?;
+        tokio::fs::write(Self::get_path(data_dir), data).await?;
+        Ok(())
+    }
+
+    /// Gets the info for a level, or the default if there is none.
+    pub fn get_info(&self, path: &Path) -> LevelInfo {
+        self.levels.get(path).cloned().unwrap_or_default()
+    }
+
+    /// Sets the info for a level, removing it if it is the default.
+    pub fn set_info(&mut self, path: &Path, info: LevelInfo) {
+        if info == LevelInfo::default() {
+            self.levels.remove(path);
+        } else {
+            self.levels.insert(path.to_path_buf(), info);
+        }
+    }
+
+    /// Adds a new empty playlist, returning its index.
+    pub fn add_playlist(&mut self, name: &str) -> Result<usize, LibraryError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(LibraryError::EmptyPlaylistName);
+        }
+        if self.playlists.iter().any(|playlist| playlist.name == name) {
+            return Err(LibraryError::DuplicatePlaylist(name.into()));
+        }
+
+        self.playlists.push(Playlist {
+            name: name.into(),
+            levels: Vec::new(),
+        });
+
+        Ok(self.playlists.len() - 1)
+    }
+}
+
+/// Finds the level files in the sync dir and the library folders, sorted by name.
+/// Folders that do not exist are skipped.
+pub async fn scan_levels(
+    sync_dir: &Path,
+    folders: &[PathBuf],
+) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut levels = Vec::new();
+    let mut dirs = Vec::with_capacity(folders.len() + 1);
+    for dir in std::iter::once(sync_dir).chain(folders.iter().map(PathBuf::as_path)) {
+        if is_dir(dir).await {
+            dirs.push(dir.to_path_buf());
+        }
+    }
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if is_dir(&path).await {
+                dirs.push(path);
+            } else if path.extension().map_or(false, |ext| ext == "txt") {
+                levels.push(path);
+            }
+        }
+    }
+    levels.sort_by_cached_key(|path| (get_level_name(path).to_lowercase(), path.clone()));
+    // A folder may be inside the sync dir or another folder.
+    levels.dedup();
+
+    Ok(levels)
+}
+
+/// Returns true if the path is a dir, following links like Path::is_dir.
+async fn is_dir(path: &Path) -> bool {
+    tokio::fs::metadata(path)
+        .await
+        .map_or(false, |metadata| metadata.is_dir())
+}
+
+/// Gets the name of a level, which is its file name without the extension.
+pub fn get_level_name(path: &Path) -> Cow<'_, str> {
+    path.file_stem()
+        .map_or(Cow::Borrowed(""), |file_stem| file_stem.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::get_test_dir;
+    use tokio::runtime::Runtime as TokioRuntime;
+
+    fn make_playlist(levels: &[&str]) -> Playlist {
+        Playlist {
+            name: String::from("Playtest"),
+            levels: levels.iter().map(PathBuf::from).collect(),
+        }
+    }
+
+    #[test]
+    fn playlist_moves_levels() {
+        let mut playlist = make_playlist(&["a", "b", "c"]);
+
+        playlist.move_up(2);
+        assert_eq!(playlist.levels, make_playlist(&["a", "c", "b"]).levels);
+        playlist.move_down(0);
+        assert_eq!(playlist.levels, make_playlist(&["c", "a", "b"]).levels);
+
+        // Moving past either end does nothing.
+        playlist.move_up(0);
+        playlist.move_down(2);
+        playlist.move_up(3);
+        playlist.move_down(3);
+        assert_eq!(playlist.levels, make_playlist(&["c", "a", "b"]).levels);
+    }
+
+    #[test]
+    fn add_playlist_validates_name() {
+        let mut library = Library::default();
+
+        assert_eq!(library.add_playlist(" Playtest ").ok(), Some(0));
+        assert_eq!(library.playlists[0].name, "Playtest");
+        assert_eq!(library.add_playlist("Other").ok(), Some(1));
+
+        assert!(matches!(
+            library.add_playlist("  "),
+            Err(LibraryError::EmptyPlaylistName)
+        ));
+        assert!(matches!(
+            library.add_playlist("Playtest"),
+            Err(LibraryError::DuplicatePlaylist(name)) if name == "Playtest"
+        ));
+        assert_eq!(library.playlists.len(), 2);
+    }
+
+    #[test]
+    fn set_info_drops_default_info() {
+        let mut library = Library::default();
+        let path = Path::new("level.txt");
+        let info = LevelInfo {
+            favorite: true,
+            tags: vec![String::from("hard")],
+            notes: String::new(),
+        };
+
+        library.set_info(path, info.clone());
+        assert_eq!(library.get_info(path), info);
+        assert_eq!(library.levels.len(), 1);
+
+        library.set_info(path, LevelInfo::default());
+        assert_eq!(library.get_info(path), LevelInfo::default());
+        assert!(library.levels.is_empty());
+    }
+
+    #[test]
+    fn export_prefixes_levels_in_order() {
+        let dir = get_test_dir("playlist-export");
+        let mut playlist = make_playlist(&[]);
+        for name in &["b.txt", "a.txt"] {
+            let path = dir.join(name);
+            std::fs::write(&path, name).expect("failed to write level");
+            playlist.levels.push(path);
+        }
+
+        let mut tokio_rt = TokioRuntime::new().expect("failed to start tokio");
+        let target_dir = dir.join("export");
+        let paths = tokio_rt
+            .block_on(playlist.export(&target_dir))
+            .expect("failed to export playlist");
+
+        assert_eq!(
+            paths,
+            vec![
+                target_dir.join("001 - b.txt"),
+                target_dir.join("002 - a.txt")
+            ]
+        );
+        for (path, name) in paths.iter().zip(&["b.txt", "a.txt"]) {
+            assert_eq!(
+                std::fs::read_to_string(path).expect("failed to read export"),
+                *name
+            );
+        }
+
+        assert!(matches!(
+            tokio_rt.block_on(make_playlist(&[]).export(&target_dir)),
+            Err(LibraryError::EmptyPlaylist)
+        ));
+    }
+}
@@ -0,0 +1,285 @@
+use crate::config::LogConfig;
+use log::{
+    Level,
+    LevelFilter,
+    Log,
+    Metadata,
+    Record,
+};
+use parking_lot::Mutex;
+use std::{
+    fs::{
+        File,
+        OpenOptions,
+    },
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// The name of the current log file in the log dir.
+const LOG_FILE_NAME: &str = "launcher.log";
+
+#[derive(Debug)]
+pub enum LoggerError {
+    Io(std::io::Error),
+    SetLogger(log::SetLoggerError),
+}
+
+impl From<std::io::Error> for LoggerError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<log::SetLoggerError> for LoggerError {
+    fn from(e: log::SetLoggerError) -> Self {
+        Self::SetLogger(e)
+    }
+}
+
+impl std::fmt::Display for LoggerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => e.fmt(f),
+            Self::SetLogger(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for LoggerError {}
+
+/// Gets the dir logs are written to.
+pub fn get_log_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("logs")
+}
+
+/// Gets the path of the current log file.
+pub fn get_log_path(data_dir: &Path) -> PathBuf {
+    get_log_dir(data_dir).join(LOG_FILE_NAME)
+}
+
+/// Gets the path of the nth rotated log file. The current log file is index 0.
+fn get_rotated_log_path(log_dir: &Path, index: u32) -> PathBuf {
+    if index == 0 {
+        log_dir.join(LOG_FILE_NAME)
+    } else {
+        log_dir.join(format!("launcher.{}.log", index))
+    }
+}
+
+/// A log file that is rotated once it grows past a size limit.
+struct RotatingFile {
+    log_dir: PathBuf,
+    file: File,
+    size: u64,
+
+    max_file_size: u64,
+    max_files: u32,
+}
+
+impl RotatingFile {
+    fn open(log_dir: PathBuf, max_file_size: u64, max_files: u32) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&log_dir)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(get_rotated_log_path(&log_dir, 0))?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            log_dir,
+            file,
+            size,
+
+            max_file_size,
+            max_files: max_files.max(1),
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let len = line.len() as u64;
+        if self.size > 0 && self.size + len > self.max_file_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += len;
+
+        Ok(())
+    }
+
+    /// Shifts every log file up by one index, dropping the oldest, and starts a new current file.
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+
+        let oldest = get_rotated_log_path(&self.log_dir, self.max_files - 1);
+        if oldest.exists() {
+            std::fs::remove_file(&oldest)?;
+        }
+
+        for index in (0..self.max_files - 1).rev() {
+            let from = get_rotated_log_path(&self.log_dir, index);
+            if from.exists() {
+                std::fs::rename(&from, get_rotated_log_path(&self.log_dir, index + 1))?;
+            }
+        }
+
+        self.file = File::create(get_rotated_log_path(&self.log_dir, 0))?;
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+/// A logger that writes to stderr and a rotating log file.
+struct FileLogger {
+    file: Mutex<RotatingFile>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format_line(record.level(), record.target(), record.args());
+        eprint!("{}", line);
+
+        // There is nowhere to report a failure to log, so ignore it.
+        let _ = self.file.lock().write_line(&line).is_ok();
+    }
+
+    fn flush(&self) {
+        let _ = self.file.lock().file.flush().is_ok();
+    }
+}
+
+/// Formats a log line, ending in a newline.
+fn format_line(level: Level, target: &str, message: &dyn std::fmt::Display) -> String {
+    format!(
+        "{} {:<5} [{}] {}\n",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+        level,
+        target,
+        message
+    )
+}
+
+/// Writes an error to stderr and appends it to the current log file in the data dir, if given,
+/// without going through the logger.
+///
+/// This is for the panic hook. The panic may have happened while the logger was locked, so logging normally could deadlock.
+pub fn write_error_unlocked(
+    data_dir: Option<&Path>,
+    target: &str,
+    message: &dyn std::fmt::Display,
+) {
+    let line = format_line(Level::Error, target, message);
+    eprint!("{}", line);
+
+    // There is nowhere to report a failure to log, so ignore it.
+    if let Some(data_dir) = data_dir {
+        let _ = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(get_log_path(data_dir))
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .is_ok();
+    }
+}
+
+/// Installs the global logger, writing to the log dir in the data dir.
+pub fn init(data_dir: &Path, config: &LogConfig) -> Result<(), LoggerError> {
+    let file = RotatingFile::open(
+        get_log_dir(data_dir),
+        config.max_file_size,
+        config.max_files,
+    )?;
+
+    log::set_boxed_logger(Box::new(FileLogger {
+        file: Mutex::new(file),
+    }))?;
+    set_level(config.level);
+
+    Ok(())
+}
+
+/// Sets the max level of messages that are logged.
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::get_test_dir;
+
+    fn read_log(log_dir: &Path, index: u32) -> Option<String> {
+        std::fs::read_to_string(get_rotated_log_path(log_dir, index)).ok()
+    }
+
+    #[test]
+    fn rotates_at_max_file_size() {
+        let log_dir = get_test_dir("logger-rotate");
+        let mut file = RotatingFile::open(log_dir.clone(), 10, 3).expect("failed to open log");
+
+        file.write_line("aaaa\n").expect("failed to write");
+        file.write_line("bbbb\n").expect("failed to write");
+        assert_eq!(read_log(&log_dir, 0).as_deref(), Some("aaaa\nbbbb\n"));
+        assert_eq!(read_log(&log_dir, 1), None);
+
+        // The file would grow past 10 bytes, so it is rotated first.
+        file.write_line("cccc\n").expect("failed to write");
+        assert_eq!(read_log(&log_dir, 0).as_deref(), Some("cccc\n"));
+        assert_eq!(read_log(&log_dir, 1).as_deref(), Some("aaaa\nbbbb\n"));
+
+        // A line longer than the max size is still written to an empty file.
+        let mut file =
+            RotatingFile::open(get_test_dir("logger-long-line"), 4, 3).expect("failed to open log");
+        file.write_line("long line\n").expect("failed to write");
+        assert_eq!(file.size, 10);
+
+        std::fs::remove_dir_all(&log_dir).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn prunes_old_files() {
+        let log_dir = get_test_dir("logger-prune");
+        let mut file = RotatingFile::open(log_dir.clone(), 5, 3).expect("failed to open log");
+
+        for line in ["1111\n", "2222\n", "3333\n", "4444\n"].iter() {
+            file.write_line(line).expect("failed to write");
+        }
+        assert_eq!(read_log(&log_dir, 0).as_deref(), Some("4444\n"));
+        assert_eq!(read_log(&log_dir, 1).as_deref(), Some("3333\n"));
+        assert_eq!(read_log(&log_dir, 2).as_deref(), Some("2222\n"));
+        assert_eq!(read_log(&log_dir, 3), None);
+
+        std::fs::remove_dir_all(&log_dir).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn open_continues_current_file() {
+        let log_dir = get_test_dir("logger-reopen");
+        RotatingFile::open(log_dir.clone(), 10, 2)
+            .expect("failed to open log")
+            .write_line("aaaa\n")
+            .expect("failed to write");
+
+        let mut file = RotatingFile::open(log_dir.clone(), 10, 2).expect("failed to open log");
+        assert_eq!(file.size, 5);
+        file.write_line("bbbbbb\n").expect("failed to write");
+        assert_eq!(read_log(&log_dir, 0).as_deref(), Some("bbbbbb\n"));
+        assert_eq!(read_log(&log_dir, 1).as_deref(), Some("aaaa\n"));
+
+        std::fs::remove_dir_all(&log_dir).expect("failed to remove test dir");
+    }
+}
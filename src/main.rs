@@ -1,9 +1,13 @@
-mod config;
-pub mod steamworks_util;
+mod cli;
+mod crash;
+mod diagnostics;
+mod publish;
 mod ui;
-mod util;
 
-use crate::ui::App;
+use crate::{
+    diagnostics::GpuInfo,
+    ui::App,
+};
 use conrod_core::{
     text::Font,
     Theme,
@@ -15,11 +19,18 @@ use glutin::window::{
 };
 use glutin_window::GlutinWindow;
 use image::GenericImageView;
+use log::{
+    error,
+    info,
+};
 use piston_window::{
     texture::UpdateTexture,
+    Event,
     EventLoop,
+    FileDrag,
     G2d,
     G2dTexture,
+    Input,
     OpenGL,
     PistonWindow,
     Texture,
@@ -28,12 +39,26 @@ use piston_window::{
     Window,
     WindowSettings,
 };
+use skeleton_sprint_launcher::{
+    config,
+    level,
+    library,
+    logger,
+    pack,
+    preview,
+    steamworks_util,
+    sync,
+    util,
+    workshop,
+};
 use std::error::Error as StdError;
 
 const COVER_IMAGE_DATA: &[u8] = include_bytes!("../assets/cover.png");
 const FONT_DATA: &[u8] = include_bytes!("../assets/fonts/bolonewt/bolonewt.ttf");
 const ICON_DATA: &[u8] = include_bytes!("../assets/icon.ico");
 
+const CONFIG_PATH: &str = "./config.toml";
+
 const WINDOW_WIDTH: u32 = 800;
 const WINDOW_HEIGHT: u32 = 600;
 
@@ -115,10 +140,29 @@ fn make_piston_window(icon: Icon) -> Result<PistonWindow<GlutinWindow>, Box<dyn
 }
 
 fn main() {
+    let options: crate::cli::Options = argh::from_env();
+    if let Some(command) = options.command.as_ref() {
+        std::process::exit(crate::cli::run(&options, command));
+    }
+
+    let config_path = options.config;
+    let config = match crate::config::load_from_file(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load config: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = crate::logger::init(config.get_data_dir(), &config.log) {
+        eprintln!("Failed to init logger: {}", e);
+    }
+    crate::crash::install_panic_hook(&config);
+
     let font = match Font::from_bytes(FONT_DATA) {
         Ok(font) => font,
         Err(e) => {
-            eprintln!("Failed to load font: {}", e);
+            error!("Failed to load font: {}", e);
             return;
         }
     };
@@ -126,7 +170,7 @@ fn main() {
     let icon = match load_icon(ICON_DATA) {
         Ok(icon) => icon,
         Err(e) => {
-            eprintln!("Failed to load icon: {}", e);
+            error!("Failed to load icon: {}", e);
             return;
         }
     };
@@ -134,26 +178,32 @@ fn main() {
     let cover_image = match image::load_from_memory(COVER_IMAGE_DATA) {
         Ok(cover_image) => cover_image.into_rgba8(),
         Err(e) => {
-            eprintln!("Failed to load cover image: {}", e);
+            error!("Failed to load cover image: {}", e);
             return;
         }
     };
 
-    let config = match crate::config::load_from_file("./config.toml") {
-        Ok(config) => config,
+    let mut window = match make_piston_window(icon) {
+        Ok(window) => window,
         Err(e) => {
-            eprintln!("Failed to load config: {}", e);
+            error!("Failed to make a window: {}", e);
             return;
         }
     };
 
-    let mut window = match make_piston_window(icon) {
-        Ok(window) => window,
-        Err(e) => {
-            eprintln!("Failed to make a window: {}", e);
-            return;
+    let gpu_info = {
+        let info = window.device.get_info();
+        GpuInfo {
+            vendor: info.platform_name.vendor.into(),
+            renderer: info.platform_name.renderer.into(),
+            version: format!("{:?}", info.version),
+            shading_language_version: format!("{:?}", info.shading_language),
         }
     };
+    info!(
+        "Using OpenGL {} on {} ({})",
+        gpu_info.version, gpu_info.renderer, gpu_info.vendor
+    );
 
     let mut texture_context = window.create_texture_context();
     let texture_settings = TextureSettings::new();
@@ -162,7 +212,7 @@ fn main() {
         match Texture::from_image(&mut texture_context, &cover_image, &texture_settings) {
             Ok(cover_image) => cover_image,
             Err(e) => {
-                eprintln!("Failed to load cover_image into a texture: {}", e);
+                error!("Failed to load cover_image into a texture: {}", e);
                 return;
             }
         };
@@ -170,10 +220,10 @@ fn main() {
     let mut image_map = conrod_core::image::Map::new();
     let cover_image = image_map.insert(cover_image);
 
-    let mut app = match App::new(config, cover_image) {
+    let mut app = match App::new(config, config_path, gpu_info, cover_image) {
         Ok(app) => app,
         Err(e) => {
-            eprintln!("Failed to init app: {}", e);
+            error!("Failed to init app: {}", e);
             return;
         }
     };
@@ -184,7 +234,7 @@ fn main() {
     ui.clear_with(conrod_core::color::Color::Rgba(0.0, 0.0, 0.0, 1.0));
     // ui.set_num_redraw_frames(10);
 
-    let ids = self::ui::Ids::new(ui.widget_id_generator());
+    let mut ids = self::ui::Ids::new(ui.widget_id_generator());
     ui.fonts.insert(font);
 
     let mut text_vertex_data = Vec::new();
@@ -228,11 +278,49 @@ fn main() {
             ui.handle_event(e);
         }
 
+        if let Event::Input(Input::FileDrag(FileDrag::Drop(path)), _) = &event {
+            app.import_pack(path);
+        }
+
         event.update(|_| {
             app.update();
 
+            for (published_file_id, thumbnail) in app.take_decoded_previews() {
+                match Texture::from_image(&mut texture_context, &thumbnail, &texture_settings) {
+                    Ok(texture) => {
+                        let image_id = image_map.insert(texture);
+                        app.insert_preview_image(published_file_id, image_id);
+                    }
+                    Err(e) => {
+                        error!(
+                            "Failed to load preview for {} into a texture: {}",
+                            published_file_id, e
+                        );
+                    }
+                }
+            }
+
+            for (published_file_id, preview) in app.take_rendered_level_previews() {
+                match Texture::from_image(&mut texture_context, &preview, &texture_settings) {
+                    Ok(texture) => {
+                        let image_id = image_map.insert(texture);
+                        if let Some(old_image_id) =
+                            app.insert_level_preview_image(published_file_id, image_id)
+                        {
+                            image_map.remove(old_image_id);
+                        }
+                    }
+                    Err(e) => {
+                        error!(
+                            "Failed to load level preview for {} into a texture: {}",
+                            published_file_id, e
+                        );
+                    }
+                }
+            }
+
             let mut ui = ui.set_widgets();
-            ui::gui(&mut ui, &ids, &mut app);
+            ui::gui(&mut ui, &mut ids, &mut app);
 
             if !first_draw && !opened_window {
                 let window = window.window.ctx.window();
@@ -284,6 +372,8 @@ fn main() {
             }
         });
     }
+
+    app.flush_library();
 }
 
 fn texture_from_image<T>(img: &T) -> &T {
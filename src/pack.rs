@@ -0,0 +1,501 @@
+use crate::{
+    level::{
+        Level,
+        LevelError,
+    },
+    library::get_level_name,
+    sync::SyncManifest,
+    util::{
+        get_checksum,
+        sanitize_file_name,
+    },
+    workshop::WorkshopItem,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashSet,
+    error::Error as StdError,
+    ffi::OsStr,
+    fs::File,
+    io::{
+        Read,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use zip::{
+    result::ZipError,
+    write::FileOptions,
+    ZipArchive,
+    ZipWriter,
+};
+
+/// The version of the pack format written by this launcher.
+pub const PACK_VERSION: u32 = 1;
+
+/// The name of the manifest in a pack.
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// The dir in a pack that holds the level files.
+const LEVEL_DIR: &str = "levels";
+
+#[derive(Debug)]
+pub enum PackError {
+    Io(std::io::Error),
+    Zip(ZipError),
+    Json(serde_json::Error),
+
+    /// The pack has no manifest.
+    MissingManifest,
+
+    /// The pack was made by a newer launcher.
+    UnsupportedVersion(u32),
+
+    /// A level in the manifest has a file name that is not a plain file name.
+    InvalidFileName(String),
+
+    /// More than one level in the manifest has the same file name.
+    DuplicateFileName(String),
+
+    /// A level's data does not match the checksum in the manifest.
+    ChecksumMismatch(String),
+
+    /// A level is not a playable level.
+    InvalidLevel {
+        file: String,
+        error: LevelError,
+    },
+
+    EmptyPack,
+}
+
+impl From<std::io::Error> for PackError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ZipError> for PackError {
+    fn from(e: ZipError) -> Self {
+        Self::Zip(e)
+    }
+}
+
+impl From<serde_json::Error> for PackError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl std::fmt::Display for PackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => e.fmt(f),
+            Self::Zip(e) => e.fmt(f),
+            Self::Json(e) => e.fmt(f),
+
+            Self::MissingManifest => write!(f, "The pack has no manifest"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "The pack is version {}, but only up to version {} is supported",
+                version, PACK_VERSION
+            ),
+            Self::InvalidFileName(file) => write!(f, "Invalid level file name '{}'", file),
+            Self::DuplicateFileName(file) => {
+                write!(f, "'{}' is in the pack more than once", file)
+            }
+            Self::ChecksumMismatch(file) => {
+                write!(f, "'{}' does not match its checksum", file)
+            }
+            Self::InvalidLevel { file, error } => write!(f, "'{}' is invalid: {}", file, error),
+            Self::EmptyPack => write!(f, "The pack has no levels"),
+        }
+    }
+}
+
+impl StdError for PackError {}
+
+/// The manifest of a level pack, stored as json in the pack.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PackManifest {
+    pub version: u32,
+    pub levels: Vec<PackLevel>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PackLevel {
+    pub title: String,
+
+    /// The workshop author of the level, if known.
+    pub author: Option<String>,
+
+    /// The workshop item the level came from, if any.
+    pub published_file_id: Option<u64>,
+
+    /// The file name of the level in the pack's level dir.
+    pub file: String,
+
+    /// The sha256 of the level file, as hex.
+    pub checksum: String,
+}
+
+/// A level file to put in a pack.
+#[derive(Debug)]
+pub struct PackSource {
+    pub title: String,
+    pub author: Option<String>,
+    pub published_file_id: Option<u64>,
+    pub path: PathBuf,
+}
+
+/// Gets the dir exported packs are written to by default.
+pub fn get_pack_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("packs")
+}
+
+/// Makes a path for a new pack in the pack dir.
+pub fn get_default_pack_path(data_dir: &Path) -> PathBuf {
+    let now = chrono::Local::now();
+    get_pack_dir(data_dir).join(format!("pack-{}.zip", now.format("%Y%m%d-%H%M%S")))
+}
+
+/// Gets the dir packs are installed to in the sync dir.
+pub fn get_imported_dir(sync_dir: &Path) -> PathBuf {
+    sync_dir.join("imported")
+}
+
+/// Makes pack sources for level files, named after the files.
+/// Workshop info is filled in for levels that were synced.
+pub fn get_pack_sources(
+    paths: &[PathBuf],
+    manifest: &SyncManifest,
+    workshop_items: &[WorkshopItem],
+) -> Vec<PackSource> {
+    paths
+        .iter()
+        .map(|path| {
+            let published_file_id = manifest
+                .items
+                .iter()
+                .find(|item| item.path == *path)
+                .map(|item| item.published_file_id);
+            let author = published_file_id.and_then(|published_file_id| {
+                workshop_items
+                    .iter()
+                    .find(|item| item.published_file_id == published_file_id)
+                    .map(|item| item.author.clone())
+            });
+
+            PackSource {
+                title: get_level_name(path).into_owned(),
+                author,
+                published_file_id,
+                path: path.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Writes a pack of the given levels to the path.
+pub fn export_pack(path: &Path, sources: &[PackSource]) -> Result<PackManifest, PackError> {
+    if sources.is_empty() {
+        return Err(PackError::EmptyPack);
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = FileOptions::default();
+
+    let mut manifest = PackManifest {
+        version: PACK_VERSION,
+        levels: Vec::with_capacity(sources.len()),
+    };
+    for source in sources.iter() {
+        let data = std::fs::read(&source.path)?;
+
+        // Levels from different folders may share a file name.
+        let file_name = source.path.file_name().map_or_else(
+            || format!("{}.txt", source.title),
+            |file_name| file_name.to_string_lossy().into_owned(),
+        );
+        let mut file = file_name.clone();
+        let mut n = 1;
+        while manifest.levels.iter().any(|level| level.file == file) {
+            n += 1;
+            file = format!("{}_{}", n, file_name);
+        }
+
+        zip.start_file(format!("{}/{}", LEVEL_DIR, file), options)?;
+        zip.write_all(&data)?;
+
+        manifest.levels.push(PackLevel {
+            title: source.title.clone(),
+            author: source.author.clone(),
+            published_file_id: source.published_file_id,
+            file,
+            checksum: get_checksum(&data),
+        });
+    }
+
+    zip.start_file(MANIFEST_FILE_NAME, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    zip.finish()?;
+
+    Ok(manifest)
+}
+
+/// Reads a pack and checks that every level matches its checksum and is playable.
+/// Returns the manifest and the data of each level, in manifest order.
+pub fn read_pack(path: &Path) -> Result<(PackManifest, Vec<Vec<u8>>), PackError> {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+
+    let manifest: PackManifest = {
+        let mut file = match zip.by_name(MANIFEST_FILE_NAME) {
+            Ok(file) => file,
+            Err(ZipError::FileNotFound) => return Err(PackError::MissingManifest),
+            Err(e) => return Err(e.into()),
+        };
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        serde_json::from_slice(&data)?
+    };
+    if manifest.version > PACK_VERSION {
+        return Err(PackError::UnsupportedVersion(manifest.version));
+    }
+    if manifest.levels.is_empty() {
+        return Err(PackError::EmptyPack);
+    }
+
+    let mut files = HashSet::with_capacity(manifest.levels.len());
+    let mut level_data = Vec::with_capacity(manifest.levels.len());
+    for level in manifest.levels.iter() {
+        let is_file_name = Path::new(&level.file).file_name() == Some(OsStr::new(&level.file));
+        if !is_file_name {
+            return Err(PackError::InvalidFileName(level.file.clone()));
+        }
+
+        // A later level would overwrite an earlier one when installed.
+        if !files.insert(level.file.as_str()) {
+            return Err(PackError::DuplicateFileName(level.file.clone()));
+        }
+
+        let mut file = zip.by_name(&format!("{}/{}", LEVEL_DIR, level.file))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        if !get_checksum(&data).eq_ignore_ascii_case(&level.checksum) {
+            return Err(PackError::ChecksumMismatch(level.file.clone()));
+        }
+        Level::parse_bytes(&data)
+            .and_then(|parsed| parsed.validate())
+            .map_err(|error| PackError::InvalidLevel {
+                file: level.file.clone(),
+                error,
+            })?;
+
+        level_data.push(data);
+    }
+
+    Ok((manifest, level_data))
+}
+
+/// Installs the levels of a pack to a dir named after the pack in the imported dir of the sync dir.
+/// Nothing is installed unless every level in the pack is valid.
+/// Returns the paths of the installed levels.
+pub fn import_pack(path: &Path, sync_dir: &Path) -> Result<Vec<PathBuf>, PackError> {
+    let (manifest, level_data) = read_pack(path)?;
+
+    let pack_name = path
+        .file_stem()
+        .map_or_else(|| "pack".into(), |file_stem| file_stem.to_string_lossy());
+    let install_dir = get_imported_dir(sync_dir).join(sanitize_file_name(&pack_name));
+    std::fs::create_dir_all(&install_dir)?;
+
+    let mut paths = Vec::with_capacity(level_data.len());
+    for (level, data) in manifest.levels.iter().zip(level_data) {
+        let path = install_dir.join(&level.file);
+        std::fs::write(&path, data)?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        level::tests::make_level,
+        util::get_test_dir,
+    };
+
+    /// Writes level files to the dir, returning pack sources for them.
+    fn make_sources(dir: &Path, names: &[&str]) -> Vec<PackSource> {
+        let data = make_level(&[(1, 16, "P0"), (30, 16, "E0"), (5, 17, "B0")]);
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let path = dir.join(name);
+                std::fs::create_dir_all(path.parent().expect("level has no parent"))
+                    .expect("failed to create level dir");
+                std::fs::write(&path, &data).expect("failed to write level");
+                PackSource {
+                    title: get_level_name(&path).into_owned(),
+                    author: Some(String::from("Author")),
+                    published_file_id: Some(i as u64),
+                    path,
+                }
+            })
+            .collect()
+    }
+
+    /// Writes a pack with the manifest and the given files in its level dir.
+    fn write_pack(path: &Path, manifest: &PackManifest, files: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(File::create(path).expect("failed to create pack"));
+        let options = FileOptions::default();
+        for (name, data) in files.iter() {
+            zip.start_file(format!("{}/{}", LEVEL_DIR, name), options)
+                .expect("failed to start level");
+            zip.write_all(data).expect("failed to write level");
+        }
+        zip.start_file(MANIFEST_FILE_NAME, options)
+            .expect("failed to start manifest");
+        zip.write_all(&serde_json::to_vec(manifest).expect("failed to serialize manifest"))
+            .expect("failed to write manifest");
+        zip.finish().expect("failed to finish pack");
+    }
+
+    /// Makes a manifest with a level for each file, with checksums of the data.
+    fn make_manifest(version: u32, files: &[(&str, &[u8])]) -> PackManifest {
+        PackManifest {
+            version,
+            levels: files
+                .iter()
+                .map(|(name, data)| PackLevel {
+                    title: String::from(*name),
+                    author: None,
+                    published_file_id: None,
+                    file: String::from(*name),
+                    checksum: get_checksum(data),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn export_import_round_trip() {
+        let dir = get_test_dir("pack-round-trip");
+        let sources = make_sources(&dir, &["a/level.txt", "b/level.txt", "other.txt"]);
+        let pack_path = dir.join("packs").join("My Pack.zip");
+
+        let exported = export_pack(&pack_path, &sources).expect("failed to export pack");
+        let files: Vec<&str> = exported
+            .levels
+            .iter()
+            .map(|level| level.file.as_str())
+            .collect();
+        assert_eq!(files, vec!["level.txt", "2_level.txt", "other.txt"]);
+
+        let (read, _) = read_pack(&pack_path).expect("failed to read pack");
+        assert_eq!(read.version, PACK_VERSION);
+        for (read_level, source) in read.levels.iter().zip(&sources) {
+            assert_eq!(read_level.title, source.title);
+            assert_eq!(read_level.author, source.author);
+            assert_eq!(read_level.published_file_id, source.published_file_id);
+        }
+
+        let sync_dir = dir.join("sync");
+        let paths = import_pack(&pack_path, &sync_dir).expect("failed to import pack");
+        let install_dir = get_imported_dir(&sync_dir).join("My Pack");
+        assert_eq!(
+            paths,
+            vec![
+                install_dir.join("level.txt"),
+                install_dir.join("2_level.txt"),
+                install_dir.join("other.txt")
+            ]
+        );
+        for (path, source) in paths.iter().zip(&sources) {
+            assert_eq!(
+                std::fs::read(path).expect("failed to read installed level"),
+                std::fs::read(&source.path).expect("failed to read source level")
+            );
+        }
+    }
+
+    #[test]
+    fn read_pack_rejects_checksum_mismatch() {
+        let dir = get_test_dir("pack-checksum");
+        let data = make_level(&[(1, 16, "P0"), (30, 16, "E0"), (5, 17, "B0")]);
+        let manifest = make_manifest(PACK_VERSION, &[("level.txt", data.as_bytes())]);
+        let pack_path = dir.join("pack.zip");
+        write_pack(&pack_path, &manifest, &[("level.txt", b"tampered")]);
+
+        assert!(matches!(
+            read_pack(&pack_path),
+            Err(PackError::ChecksumMismatch(file)) if file == "level.txt"
+        ));
+
+        // Nothing is installed from a bad pack.
+        let sync_dir = dir.join("sync");
+        assert!(import_pack(&pack_path, &sync_dir).is_err());
+        assert!(!get_imported_dir(&sync_dir).exists());
+    }
+
+    #[test]
+    fn read_pack_rejects_bad_file_names() {
+        let dir = get_test_dir("pack-file-names");
+        let data = make_level(&[(1, 16, "P0"), (30, 16, "E0"), (5, 17, "B0")]);
+        let pack_path = dir.join("pack.zip");
+
+        for file in &["../level.txt", "a/level.txt", "..", ""] {
+            let files = [(*file, data.as_bytes())];
+            write_pack(&pack_path, &make_manifest(PACK_VERSION, &files), &files);
+            assert!(
+                matches!(
+                    read_pack(&pack_path),
+                    Err(PackError::InvalidFileName(name)) if name == *file
+                ),
+                "'{}' was not rejected",
+                file
+            );
+        }
+
+        let files = [
+            ("level.txt", data.as_bytes()),
+            ("level.txt", data.as_bytes()),
+        ];
+        write_pack(
+            &pack_path,
+            &make_manifest(PACK_VERSION, &files),
+            &files[..1],
+        );
+        assert!(matches!(
+            read_pack(&pack_path),
+            Err(PackError::DuplicateFileName(name)) if name == "level.txt"
+        ));
+    }
+
+    #[test]
+    fn read_pack_rejects_newer_version() {
+        let dir = get_test_dir("pack-version");
+        let data = make_level(&[(1, 16, "P0"), (30, 16, "E0"), (5, 17, "B0")]);
+        let files = [("level.txt", data.as_bytes())];
+        let pack_path = dir.join("pack.zip");
+        write_pack(&pack_path, &make_manifest(PACK_VERSION + 1, &files), &files);
+
+        assert!(matches!(
+            read_pack(&pack_path),
+            Err(PackError::UnsupportedVersion(version)) if version == PACK_VERSION + 1
+        ));
+    }
+}
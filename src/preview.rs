@@ -0,0 +1,268 @@
+use image::RgbaImage;
+use std::{
+    error::Error as StdError,
+    future::Future,
+    path::{
+        Path,
+        PathBuf,
+    },
+    pin::Pin,
+};
+
+/// The width and height thumbnails are scaled to fit in.
+pub const THUMBNAIL_SIZE: u32 = 64;
+
+pub type FetchError = Box<dyn StdError + Send + Sync>;
+pub type FetchFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<u8>, FetchError>> + Send + 'a>>;
+
+/// Something that can download preview images.
+pub trait Fetcher: Send + Sync {
+    /// Fetches the data at the url.
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a>;
+}
+
+/// Fetches preview images over http.
+#[derive(Debug, Default)]
+pub struct HttpFetcher {
+    client: reqwest::Client,
+}
+
+impl Fetcher for HttpFetcher {
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+        Box::pin(async move {
+            let response = self.client.get(url).send().await?.error_for_status()?;
+            Ok(response.bytes().await?.to_vec())
+        })
+    }
+}
+
+/// Fetches preview images from a local dir, using the last segment of the url as the file name.
+#[derive(Debug)]
+pub struct FileFetcher {
+    root: PathBuf,
+}
+
+impl FileFetcher {
+    /// Makes a FileFetcher that reads files from the given dir.
+    pub fn new<T: Into<PathBuf>>(root: T) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl Fetcher for FileFetcher {
+    fn fetch<'a>(&'a self, url: &'a str) -> FetchFuture<'a> {
+        Box::pin(async move {
+            let url = url.split(|c| c == '?' || c == '#').next().unwrap_or(url);
+            let file_name = url
+                .rsplit('/')
+                .next()
+                .filter(|file_name| !file_name.is_empty())
+                .ok_or("The url has no file name")?;
+
+            Ok(tokio::fs::read(self.root.join(file_name)).await?)
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum PreviewError {
+    Io(std::io::Error),
+    Fetch(FetchError),
+    Image(image::ImageError),
+}
+
+impl From<std::io::Error> for PreviewError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<FetchError> for PreviewError {
+    fn from(e: FetchError) -> Self {
+        Self::Fetch(e)
+    }
+}
+
+impl From<image::ImageError> for PreviewError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+impl std::fmt::Display for PreviewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => e.fmt(f),
+            Self::Fetch(e) => e.fmt(f),
+            Self::Image(e) => e.fmt(f),
+        }
+    }
+}
+
+impl StdError for PreviewError {}
+
+/// A disk cache of workshop item preview images.
+pub struct PreviewCache {
+    cache_dir: PathBuf,
+    fetcher: Box<dyn Fetcher>,
+}
+
+impl PreviewCache {
+    /// Makes a cache that stores images in cache_dir and downloads missing ones with the fetcher.
+    pub fn new(cache_dir: PathBuf, fetcher: Box<dyn Fetcher>) -> Self {
+        Self { cache_dir, fetcher }
+    }
+
+    /// Gets the dir previews are cached in, in the data dir.
+    pub fn get_cache_dir(data_dir: &Path) -> PathBuf {
+        data_dir.join("previews")
+    }
+
+    fn get_image_path(&self, published_file_id: u64) -> PathBuf {
+        self.cache_dir.join(format!("{}.img", published_file_id))
+    }
+
+    fn get_url_path(&self, published_file_id: u64) -> PathBuf {
+        self.cache_dir.join(format!("{}.url", published_file_id))
+    }
+
+    /// Gets the preview image data for an item, fetching it if it is not cached or the url changed.
+    pub async fn get(&self, published_file_id: u64, url: &str) -> Result<Vec<u8>, PreviewError> {
+        let image_path = self.get_image_path(published_file_id);
+        let url_path = self.get_url_path(published_file_id);
+
+        if let Ok(cached_url) = tokio::fs::read_to_string(&url_path).await {
+            if cached_url == url {
+                if let Ok(data) = tokio::fs::read(&image_path).await {
+                    return Ok(data);
+                }
+            }
+        }
+
+        let data = self.fetcher.fetch(url).await?;
+
+        tokio::fs::create_dir_all(&self.cache_dir).await?;
+        tokio::fs::write(&image_path, &data).await?;
+        tokio::fs::write(&url_path, url).await?;
+
+        Ok(data)
+    }
+}
+
+/// Decodes image data and scales it down to fit in a THUMBNAIL_SIZE square.
+pub fn decode_thumbnail(data: &[u8]) -> Result<RgbaImage, PreviewError> {
+    let image = image::load_from_memory(data)?;
+    Ok(image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).into_rgba8())
+}
+
+/// Gets and decodes the thumbnail for an item.
+pub async fn load_thumbnail(
+    preview_cache: &PreviewCache,
+    published_file_id: u64,
+    url: &str,
+) -> Result<RgbaImage, PreviewError> {
+    let data = preview_cache.get(published_file_id, url).await?;
+    Ok(decode_thumbnail(&data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::get_test_dir;
+
+    /// Makes a cache in the test dir that fetches from the source dir in it.
+    fn make_cache(dir: &Path) -> PreviewCache {
+        let source_dir = dir.join("source");
+        std::fs::create_dir_all(&source_dir).expect("failed to create source dir");
+        PreviewCache::new(dir.join("cache"), Box::new(FileFetcher::new(source_dir)))
+    }
+
+    #[test]
+    fn cache_miss_fetches() {
+        let dir = get_test_dir("preview-miss");
+        let cache = make_cache(&dir);
+        std::fs::write(dir.join("source").join("1.png"), "image").expect("failed to write");
+
+        let mut tokio_rt = tokio::runtime::Runtime::new().expect("failed to start tokio");
+        let data = tokio_rt
+            .block_on(cache.get(1, "https://example.com/previews/1.png?size=large"))
+            .expect("failed to get preview");
+        assert_eq!(data, b"image");
+        assert_eq!(
+            std::fs::read(dir.join("cache").join("1.img")).expect("image was not cached"),
+            b"image"
+        );
+
+        std::fs::remove_dir_all(&dir).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn cache_hit_does_not_fetch() {
+        let dir = get_test_dir("preview-hit");
+        let cache = make_cache(&dir);
+        let source_path = dir.join("source").join("1.png");
+        std::fs::write(&source_path, "old").expect("failed to write");
+
+        let mut tokio_rt = tokio::runtime::Runtime::new().expect("failed to start tokio");
+        let url = "https://example.com/1.png";
+        tokio_rt
+            .block_on(cache.get(1, url))
+            .expect("failed to get preview");
+
+        // The cached image is used even though the source changed, and even if it is gone.
+        std::fs::write(&source_path, "new").expect("failed to write");
+        let data = tokio_rt
+            .block_on(cache.get(1, url))
+            .expect("failed to get preview");
+        assert_eq!(data, b"old");
+
+        std::fs::remove_file(&source_path).expect("failed to remove source");
+        let data = tokio_rt
+            .block_on(cache.get(1, url))
+            .expect("failed to get preview");
+        assert_eq!(data, b"old");
+
+        std::fs::remove_dir_all(&dir).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn url_change_fetches() {
+        let dir = get_test_dir("preview-url-change");
+        let cache = make_cache(&dir);
+        std::fs::write(dir.join("source").join("1.png"), "old").expect("failed to write");
+        std::fs::write(dir.join("source").join("1-v2.png"), "new").expect("failed to write");
+
+        let mut tokio_rt = tokio::runtime::Runtime::new().expect("failed to start tokio");
+        tokio_rt
+            .block_on(cache.get(1, "https://example.com/1.png"))
+            .expect("failed to get preview");
+        let data = tokio_rt
+            .block_on(cache.get(1, "https://example.com/1-v2.png"))
+            .expect("failed to get preview");
+        assert_eq!(data, b"new");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("cache").join("1.url")).expect("url was not cached"),
+            "https://example.com/1-v2.png"
+        );
+
+        std::fs::remove_dir_all(&dir).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn missing_source_fails() {
+        let dir = get_test_dir("preview-missing");
+        let cache = make_cache(&dir);
+
+        let mut tokio_rt = tokio::runtime::Runtime::new().expect("failed to start tokio");
+        let result = tokio_rt.block_on(cache.get(1, "https://example.com/1.png"));
+        assert!(matches!(result, Err(PreviewError::Fetch(_))));
+        assert!(!dir.join("cache").join("1.img").exists());
+        assert!(!dir.join("cache").join("1.url").exists());
+
+        // A url without a file name can't be fetched from a dir.
+        let result = tokio_rt.block_on(cache.get(1, "https://example.com/"));
+        assert!(matches!(result, Err(PreviewError::Fetch(_))));
+
+        std::fs::remove_dir_all(&dir).expect("failed to remove test dir");
+    }
+}
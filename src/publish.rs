@@ -0,0 +1,404 @@
+use crate::{
+    steamworks_util::{
+        collect_query_results,
+        create_item,
+        ItemUpdateBuilder,
+        ItemUpdateError,
+        ItemUpdateWatcher,
+        ItemVisibility,
+        OneShotRecvError,
+        QueryResultEntry,
+        UgcQueryBuilder,
+        WorkshopQueryError,
+    },
+    workshop::WorkshopItem,
+};
+use log::{
+    error,
+    info,
+};
+use parking_lot::Mutex;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    error::Error as StdError,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::Arc,
+};
+use tokio::runtime::Runtime as TokioRuntime;
+
+#[derive(Debug)]
+pub enum PublishError {
+    Recieve(OneShotRecvError),
+    Steam(steamworks::SteamError),
+    Io(std::io::Error),
+    ItemUpdate(ItemUpdateError),
+
+    EmptyTitle,
+    InvalidLevelPath,
+}
+
+impl From<OneShotRecvError> for PublishError {
+    fn from(e: OneShotRecvError) -> Self {
+        Self::Recieve(e)
+    }
+}
+
+impl From<steamworks::SteamError> for PublishError {
+    fn from(e: steamworks::SteamError) -> Self {
+        Self::Steam(e)
+    }
+}
+
+impl From<std::io::Error> for PublishError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ItemUpdateError> for PublishError {
+    fn from(e: ItemUpdateError) -> Self {
+        Self::ItemUpdate(e)
+    }
+}
+
+impl std::fmt::Display for PublishError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Recieve(e) => e.fmt(f),
+            Self::Steam(e) => e.fmt(f),
+            Self::Io(e) => e.fmt(f),
+            Self::ItemUpdate(e) => e.fmt(f),
+
+            Self::EmptyTitle => write!(f, "The title is empty"),
+            Self::InvalidLevelPath => write!(f, "The level path is not a file"),
+        }
+    }
+}
+
+impl StdError for PublishError {}
+
+/// A record of the levels that were published from this launcher.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PublishedLevels {
+    pub items: Vec<PublishedLevel>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PublishedLevel {
+    pub published_file_id: u64,
+    pub title: String,
+    pub level_path: PathBuf,
+}
+
+impl PublishedLevels {
+    /// Gets the path of the published levels record in the data dir.
+    pub fn get_path(data_dir: &Path) -> PathBuf {
+        data_dir.join("published_levels.json")
+    }
+
+    /// Loads the record, returning an empty one if it does not exist.
+    pub async fn load(data_dir: &Path) -> Result<Self, std::io::Error> {
+        match tokio::fs::read(Self::get_path(data_dir)).await {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Saves the record to the data dir.
+    pub async fn save(&self, data_dir: &Path) -> Result<(), std::io::Error> {
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        tokio::fs::create_dir_all(data_dir).await?;
+        tokio::fs::write(Self::get_path(data_dir), data).await
+    }
+
+    /// Adds a level, replacing any older record with the same published file id.
+    pub fn insert(&mut self, level: PublishedLevel) {
+        self.items
+            .retain(|item| item.published_file_id != level.published_file_id);
+        self.items.push(level);
+    }
+}
+
+/// Everything needed to publish a level.
+#[derive(Debug, Clone)]
+pub struct PublishRequest {
+    pub level_path: PathBuf,
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub visibility: ItemVisibility,
+    pub preview_path: Option<PathBuf>,
+}
+
+/// Everything needed to update the content of a published level.
+#[derive(Debug, Clone)]
+pub struct UpdateRequest {
+    pub published_file_id: u64,
+    pub title: String,
+    pub level_path: PathBuf,
+    pub change_note: String,
+}
+
+/// One of the user's published items, with the local level it was published from if known.
+#[derive(Debug, Clone)]
+pub struct PublishedItem {
+    pub workshop_item: WorkshopItem,
+    pub level_path: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub enum PublishState {
+    Creating,
+    Staging,
+    Uploading(ItemUpdateWatcher),
+
+    /// The item was uploaded. The bool is true if the user still needs to accept the workshop legal agreement.
+    Done(u64, bool),
+    Failed(PublishError),
+}
+
+impl PublishState {
+    pub fn is_publishing(&self) -> bool {
+        matches!(self, Self::Creating | Self::Staging | Self::Uploading(_))
+    }
+}
+
+/// Gets the dir the content of a level is staged in before uploading.
+/// Workshop content is uploaded as a folder, so the level file is copied into its own dir.
+fn get_staging_dir(data_dir: &Path, published_file_id: u64) -> PathBuf {
+    data_dir.join("publish").join(published_file_id.to_string())
+}
+
+/// Copies a level file into an empty staging dir, returning the dir.
+pub async fn stage_level(
+    data_dir: &Path,
+    published_file_id: u64,
+    level_path: &Path,
+) -> Result<PathBuf, PublishError> {
+    let file_name = level_path
+        .file_name()
+        .filter(|_| level_path.is_file())
+        .ok_or(PublishError::InvalidLevelPath)?;
+
+    let staging_dir = get_staging_dir(data_dir, published_file_id);
+    match tokio::fs::remove_dir_all(&staging_dir).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    tokio::fs::create_dir_all(&staging_dir).await?;
+    tokio::fs::copy(level_path, staging_dir.join(file_name)).await?;
+
+    Ok(staging_dir)
+}
+
+/// Creates a workshop item for a local level and uploads it in the background.
+/// The published file id is recorded in the data dir as soon as the item is created.
+///
+/// The upload only completes if steam callbacks are run.
+pub fn spawn_publish(
+    tokio_rt: &TokioRuntime,
+    steam_client: steamworks::Client,
+    data_dir: PathBuf,
+    request: PublishRequest,
+) -> Arc<Mutex<PublishState>> {
+    let publish_state = Arc::new(Mutex::new(PublishState::Creating));
+    let publish_state_clone = publish_state.clone();
+
+    tokio_rt.spawn(async move {
+        if let Err(e) = publish(steam_client, publish_state_clone.clone(), data_dir, request).await
+        {
+            error!("Publish Failed: {}", e);
+            *publish_state_clone.lock() = PublishState::Failed(e);
+        }
+    });
+
+    publish_state
+}
+
+async fn publish(
+    steam_client: steamworks::Client,
+    publish_state: Arc<Mutex<PublishState>>,
+    data_dir: PathBuf,
+    request: PublishRequest,
+) -> Result<(), PublishError> {
+    if request.title.trim().is_empty() {
+        return Err(PublishError::EmptyTitle);
+    }
+    if !request.level_path.is_file() {
+        return Err(PublishError::InvalidLevelPath);
+    }
+
+    info!("Creating workshop item for '{}'", request.title);
+    let created_item = create_item(&steam_client).await??;
+    let published_file_id = created_item.published_file_id.0;
+    info!("Created workshop item {}", published_file_id);
+
+    // Record the id right away, so a failed upload can be retried as an update.
+    let mut published_levels = PublishedLevels::load(&data_dir).await?;
+    published_levels.insert(PublishedLevel {
+        published_file_id,
+        title: request.title.clone(),
+        level_path: request.level_path.clone(),
+    });
+    published_levels.save(&data_dir).await?;
+
+    *publish_state.lock() = PublishState::Staging;
+    let content_path = stage_level(&data_dir, published_file_id, &request.level_path).await?;
+
+    let mut item_update = ItemUpdateBuilder::new(&steam_client, created_item.published_file_id)
+        .title(request.title)
+        .description(request.description)
+        .tags(request.tags)
+        .visibility(request.visibility)
+        .content_path(content_path)
+        .change_note("Initial release".into());
+    if let Some(preview_path) = request.preview_path {
+        item_update = item_update.preview_path(preview_path);
+    }
+
+    let (watcher, future) = item_update.send()?;
+    *publish_state.lock() = PublishState::Uploading(watcher);
+
+    let needs_to_accept_agreement = future.await??;
+
+    info!("Published workshop item {}", published_file_id);
+    *publish_state.lock() = PublishState::Done(
+        published_file_id,
+        created_item.needs_to_accept_agreement || needs_to_accept_agreement,
+    );
+
+    Ok(())
+}
+
+/// Uploads a new version of a published level in the background, recording the level it came from.
+///
+/// The upload only completes if steam callbacks are run.
+pub fn spawn_update(
+    tokio_rt: &TokioRuntime,
+    steam_client: steamworks::Client,
+    data_dir: PathBuf,
+    request: UpdateRequest,
+) -> Arc<Mutex<PublishState>> {
+    let publish_state = Arc::new(Mutex::new(PublishState::Staging));
+    let publish_state_clone = publish_state.clone();
+
+    tokio_rt.spawn(async move {
+        if let Err(e) = update(steam_client, publish_state_clone.clone(), data_dir, request).await {
+            error!("Update Failed: {}", e);
+            *publish_state_clone.lock() = PublishState::Failed(e);
+        }
+    });
+
+    publish_state
+}
+
+async fn update(
+    steam_client: steamworks::Client,
+    publish_state: Arc<Mutex<PublishState>>,
+    data_dir: PathBuf,
+    request: UpdateRequest,
+) -> Result<(), PublishError> {
+    let published_file_id = request.published_file_id;
+    info!(
+        "Updating workshop item {} from '{}'",
+        published_file_id,
+        request.level_path.display()
+    );
+
+    let content_path = stage_level(&data_dir, published_file_id, &request.level_path).await?;
+
+    let mut item_update = ItemUpdateBuilder::new(
+        &steam_client,
+        steamworks::PublishedFileId(published_file_id),
+    )
+    .content_path(content_path);
+    if !request.change_note.trim().is_empty() {
+        item_update = item_update.change_note(request.change_note);
+    }
+
+    let (watcher, future) = item_update.send()?;
+    *publish_state.lock() = PublishState::Uploading(watcher);
+
+    let needs_to_accept_agreement = future.await??;
+
+    let mut published_levels = PublishedLevels::load(&data_dir).await?;
+    published_levels.insert(PublishedLevel {
+        published_file_id,
+        title: request.title,
+        level_path: request.level_path,
+    });
+    published_levels.save(&data_dir).await?;
+
+    info!("Updated workshop item {}", published_file_id);
+    *publish_state.lock() = PublishState::Done(published_file_id, needs_to_accept_agreement);
+
+    Ok(())
+}
+
+/// Queries the user's published items in the background, storing them in published_items.
+///
+/// The query only completes if steam callbacks are run.
+pub fn spawn_published_items_query(
+    tokio_rt: &TokioRuntime,
+    steam_client: &steamworks::Client,
+    data_dir: PathBuf,
+    published_items: Arc<Mutex<Vec<PublishedItem>>>,
+) -> Result<(), WorkshopQueryError> {
+    info!("Querying published workshop items");
+    // The builder lists the current user's published items by default.
+    let ugc_query_future = UgcQueryBuilder::new(steam_client)
+        .send(|res| res.map(|res| collect_query_results(&res)))?;
+    let author = steam_client.friends().name();
+
+    tokio_rt.spawn(async move {
+        let query_results: Vec<QueryResultEntry> = match ugc_query_future.await {
+            Ok(Ok(query_results)) => query_results,
+            Ok(Err(e)) => {
+                error!("Failed to query published items: {}", e);
+                return;
+            }
+            Err(e) => {
+                error!("Failed to query published items: {}", e);
+                return;
+            }
+        };
+
+        let published_levels = PublishedLevels::load(&data_dir).await.unwrap_or_else(|e| {
+            error!("Failed to load published levels: {}", e);
+            PublishedLevels::default()
+        });
+
+        *published_items.lock() = query_results
+            .iter()
+            .map(|entry| {
+                let workshop_item = WorkshopItem::from_query_result(
+                    &entry.result,
+                    author.clone(),
+                    entry.preview_url.clone(),
+                );
+                let level_path = published_levels
+                    .items
+                    .iter()
+                    .find(|level| level.published_file_id == workshop_item.published_file_id)
+                    .map(|level| level.level_path.clone());
+
+                PublishedItem {
+                    workshop_item,
+                    level_path,
+                }
+            })
+            .collect();
+    });
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::time::Duration;
+use steamworks::SteamError;
+
+/// A kind of error that may go away if the failed operation is tried again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryableError {
+    /// Steam or the file system took too long to respond.
+    Timeout,
+
+    /// Steam is busy or rate limiting requests.
+    Busy,
+
+    /// Steam could not be reached.
+    Unavailable,
+
+    /// A file operation was interrupted or would have blocked.
+    Io,
+}
+
+/// An error that may be retryable.
+pub trait Retryable {
+    /// Gets the kind of retryable error this is, or None if trying again would not help.
+    fn retryable_kind(&self) -> Option<RetryableError>;
+}
+
+impl Retryable for SteamError {
+    fn retryable_kind(&self) -> Option<RetryableError> {
+        match self {
+            Self::Timeout => Some(RetryableError::Timeout),
+            Self::Busy | Self::RateLimitExceeded => Some(RetryableError::Busy),
+            Self::ServiceUnavailable | Self::NoConnection | Self::IOFailure => {
+                Some(RetryableError::Unavailable)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Retryable for std::io::Error {
+    fn retryable_kind(&self) -> Option<RetryableError> {
+        match self.kind() {
+            std::io::ErrorKind::TimedOut => Some(RetryableError::Timeout),
+            std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock => {
+                Some(RetryableError::Io)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How often and how long to wait before trying a failed operation again.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// The max number of tries, including the first. 1 disables retrying.
+    pub max_attempts: u32,
+
+    /// The delay before the first retry in milliseconds. It doubles for every retry after that.
+    pub initial_delay_ms: u64,
+
+    /// The max delay between tries in milliseconds.
+    pub max_delay_ms: u64,
+
+    /// The kinds of errors to retry. Other errors fail right away.
+    pub retryable: Vec<RetryableError>,
+}
+
+impl RetryPolicy {
+    /// Gets how long to wait before trying again after the given attempt failed with the error,
+    /// or None if it should not be tried again. Attempts start at 1.
+    pub fn get_retry_delay<E: Retryable>(&self, attempt: u32, error: &E) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        let kind = error.retryable_kind()?;
+        if !self.retryable.contains(&kind) {
+            return None;
+        }
+
+        let delay = self
+            .initial_delay_ms
+            .saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)));
+        Some(Duration::from_millis(delay.min(self.max_delay_ms)))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay_ms: 1000,
+            max_delay_ms: 30 * 1000,
+            retryable: vec![
+                RetryableError::Timeout,
+                RetryableError::Busy,
+                RetryableError::Unavailable,
+                RetryableError::Io,
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_policy(max_attempts: u32, initial_delay_ms: u64, max_delay_ms: u64) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_delay_ms,
+            max_delay_ms,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn retry_delay_doubles() {
+        let policy = make_policy(10, 100, 60 * 1000);
+        let error = SteamError::Timeout;
+        assert_eq!(
+            policy.get_retry_delay(1, &error),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            policy.get_retry_delay(2, &error),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(
+            policy.get_retry_delay(5, &error),
+            Some(Duration::from_millis(1600))
+        );
+    }
+
+    #[test]
+    fn retry_delay_is_capped() {
+        let policy = make_policy(u32::MAX, 1000, 2500);
+        let error = SteamError::Busy;
+        assert_eq!(
+            policy.get_retry_delay(2, &error),
+            Some(Duration::from_millis(2000))
+        );
+        assert_eq!(
+            policy.get_retry_delay(3, &error),
+            Some(Duration::from_millis(2500))
+        );
+
+        // The delay saturates instead of overflowing.
+        assert_eq!(
+            policy.get_retry_delay(200, &error),
+            Some(Duration::from_millis(2500))
+        );
+    }
+
+    #[test]
+    fn retry_delay_stops_at_max_attempts() {
+        let policy = make_policy(3, 100, 60 * 1000);
+        let error = SteamError::ServiceUnavailable;
+        assert!(policy.get_retry_delay(2, &error).is_some());
+        assert_eq!(policy.get_retry_delay(3, &error), None);
+        assert_eq!(policy.get_retry_delay(4, &error), None);
+
+        let policy = make_policy(1, 100, 60 * 1000);
+        assert_eq!(policy.get_retry_delay(1, &error), None);
+    }
+
+    #[test]
+    fn retry_delay_non_retryable() {
+        let policy = make_policy(3, 100, 60 * 1000);
+        assert_eq!(policy.get_retry_delay(1, &SteamError::AccessDenied), None);
+        assert_eq!(
+            policy.get_retry_delay(1, &std::io::Error::from(std::io::ErrorKind::NotFound)),
+            None
+        );
+        assert!(policy
+            .get_retry_delay(1, &std::io::Error::from(std::io::ErrorKind::Interrupted))
+            .is_some());
+    }
+
+    #[test]
+    fn retry_delay_not_configured() {
+        let policy = RetryPolicy {
+            retryable: vec![RetryableError::Busy],
+            ..make_policy(3, 100, 60 * 1000)
+        };
+        assert!(policy.get_retry_delay(1, &SteamError::Busy).is_some());
+        assert_eq!(policy.get_retry_delay(1, &SteamError::Timeout), None);
+        assert_eq!(
+            policy.get_retry_delay(1, &std::io::Error::from(std::io::ErrorKind::TimedOut)),
+            None
+        );
+
+        let policy = RetryPolicy {
+            retryable: Vec::new(),
+            ..make_policy(3, 100, 60 * 1000)
+        };
+        assert_eq!(policy.get_retry_delay(1, &SteamError::Busy), None);
+    }
+}
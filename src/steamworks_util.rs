@@ -1,14 +1,30 @@
+use crate::retry::RetryPolicy;
+use log::warn;
+use parking_lot::Mutex;
 use std::{
     error::Error as StdError,
     future::Future,
+    mem::MaybeUninit,
+    os::raw::c_void,
+    path::PathBuf,
+    sync::Arc,
 };
 use steamworks::{
     AppIDs,
+    AppId,
+    ClientManager,
+    FileType,
+    PublishedFileId,
+    PublishedFileVisibility,
     SteamError,
+    UGCQueryType,
     UGCType,
+    UpdateStatus,
+    UpdateWatchHandle,
     UserList,
     UserListOrder,
 };
+use steamworks_sys as sys;
 pub use tokio::sync::oneshot::error::RecvError as OneShotRecvError;
 
 #[derive(Debug)]
@@ -32,6 +48,25 @@ impl std::fmt::Display for WorkshopQueryError {
 
 impl StdError for WorkshopQueryError {}
 
+/// A query result along with the data that is only available from the QueryResults it came from.
+#[derive(Debug)]
+pub struct QueryResultEntry {
+    pub result: steamworks::QueryResult,
+    pub preview_url: Option<String>,
+}
+
+/// Collects QueryResults into owned entries, so they can be sent across threads.
+pub fn collect_query_results(results: &steamworks::QueryResults<'_>) -> Vec<QueryResultEntry> {
+    results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| QueryResultEntry {
+            result,
+            preview_url: results.preview_url(i as u32),
+        })
+        .collect()
+}
+
 pub struct UgcQueryBuilder<'a> {
     client: &'a steamworks::Client,
     account_id: steamworks::AccountId,
@@ -118,4 +153,865 @@ impl<'a> UgcQueryBuilder<'a> {
 
         Ok(async { rx.await })
     }
+
+    /// Sends a UGC Query, sending it again while it fails with an error the retry policy allows.
+    /// Unlike send, mutate is only called with successful results.
+    pub fn send_with_retry<
+        O: Send + 'static,
+        M: Fn(&steamworks::QueryResults<'_>) -> O + Send + Sync + 'static,
+    >(
+        self,
+        retry_policy: RetryPolicy,
+        mutate: M,
+    ) -> Result<
+        impl Future<Output = Result<Result<O, SteamError>, OneShotRecvError>>,
+        WorkshopQueryError,
+    > {
+        let Self {
+            client,
+            account_id,
+            user_list,
+            ugc_type,
+            user_list_order,
+            app_ids,
+            page_number,
+        } = self;
+        let client = client.clone();
+        let mutate = Arc::new(mutate);
+
+        let fetch = move || {
+            let query = client.ugc().query_user(
+                account_id,
+                user_list,
+                ugc_type,
+                user_list_order,
+                app_ids,
+                page_number,
+            )?;
+
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            let mutate = mutate.clone();
+            query.fetch(move |res| {
+                let _ = tx.send(res.map(|results| mutate(&results))).is_ok();
+            });
+
+            Ok::<_, steamworks::CreateQueryError>(rx)
+        };
+
+        let rx = fetch()?;
+        Ok(retry_query(retry_policy, rx, fetch))
+    }
+}
+
+/// Waits for a query's result, fetching it again while it fails with an error the retry policy allows.
+/// If fetching again fails, the last error of the query is returned.
+async fn retry_query<O, F, E>(
+    retry_policy: RetryPolicy,
+    mut rx: tokio::sync::oneshot::Receiver<Result<O, SteamError>>,
+    mut fetch: F,
+) -> Result<Result<O, SteamError>, OneShotRecvError>
+where
+    F: FnMut() -> Result<tokio::sync::oneshot::Receiver<Result<O, SteamError>>, E>,
+    E: std::fmt::Display,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match rx.await? {
+            Ok(output) => return Ok(Ok(output)),
+            Err(e) => e,
+        };
+
+        let delay = match retry_policy.get_retry_delay(attempt, &error) {
+            Some(delay) => delay,
+            None => return Ok(Err(error)),
+        };
+        warn!(
+            "Workshop query failed on attempt {}, retrying in {:?}: {}",
+            attempt, delay, error
+        );
+        tokio::time::delay_for(delay).await;
+        attempt += 1;
+
+        // The query was already created once, so this should not fail.
+        rx = match fetch() {
+            Ok(rx) => rx,
+            Err(e) => {
+                warn!("Failed to create the workshop query again: {}", e);
+                return Ok(Err(error));
+            }
+        };
+    }
+}
+
+/// Builds a query over all workshop items of an app, not just the ones in a user's list.
+pub struct UgcAllQueryBuilder<'a> {
+    client: &'a steamworks::Client,
+    query_type: UGCQueryType,
+    ugc_type: UGCType,
+    app_ids: AppIDs,
+    page_number: u32,
+    search_text: Option<String>,
+    required_tags: Vec<String>,
+    excluded_tags: Vec<String>,
+    return_long_description: bool,
+    return_metadata: bool,
+    return_key_value_tags: bool,
+}
+
+impl<'a> UgcAllQueryBuilder<'a> {
+    /// Creates a new UgcAllQueryBuilder.
+    pub fn new(client: &'a steamworks::Client) -> Self {
+        Self {
+            client,
+            query_type: UGCQueryType::RankedByTrend,
+            ugc_type: UGCType::All,
+            app_ids: AppIDs::ConsumerAppId(client.utils().app_id()),
+            page_number: 1,
+            search_text: None,
+            required_tags: Vec::new(),
+            excluded_tags: Vec::new(),
+            return_long_description: false,
+            return_metadata: false,
+            return_key_value_tags: false,
+        }
+    }
+
+    /// Sets how results are ranked, like RankedByTrend, RankedByVote or RankedByPublicationDate. Defaults to RankedByTrend.
+    pub fn query_type(mut self, query_type: UGCQueryType) -> Self {
+        self.query_type = query_type;
+        self
+    }
+
+    /// Sets UGCType. Defaults to all.
+    pub fn ugc_type(mut self, ugc_type: UGCType) -> Self {
+        self.ugc_type = ugc_type;
+        self
+    }
+
+    /// Sets the AppIds. Defaults to the app_id of the current process as a consumer.
+    pub fn app_ids(mut self, app_ids: AppIDs) -> Self {
+        self.app_ids = app_ids;
+        self
+    }
+
+    /// Sets the page_number. It starts at 1 and defaults to 1.
+    pub fn page_number(mut self, page_number: u32) -> Self {
+        self.page_number = page_number;
+        self
+    }
+
+    /// Only returns items matching the text. Use RankedByTextSearch to rank by how well they match.
+    pub fn search_text(mut self, search_text: String) -> Self {
+        self.search_text = Some(search_text);
+        self
+    }
+
+    /// Only returns items with this tag. Can be called multiple times, items must have all required tags.
+    pub fn required_tag(mut self, tag: String) -> Self {
+        self.required_tags.push(tag);
+        self
+    }
+
+    /// Only returns items without this tag. Can be called multiple times.
+    pub fn excluded_tag(mut self, tag: String) -> Self {
+        self.excluded_tags.push(tag);
+        self
+    }
+
+    /// Returns the full description instead of a truncated one. Defaults to false.
+    pub fn return_long_description(mut self, return_long_description: bool) -> Self {
+        self.return_long_description = return_long_description;
+        self
+    }
+
+    /// Returns the developer metadata of items. Defaults to false.
+    pub fn return_metadata(mut self, return_metadata: bool) -> Self {
+        self.return_metadata = return_metadata;
+        self
+    }
+
+    /// Returns the key-value tags of items. Defaults to false.
+    pub fn return_key_value_tags(mut self, return_key_value_tags: bool) -> Self {
+        self.return_key_value_tags = return_key_value_tags;
+        self
+    }
+
+    /// Sends a UGC Query
+    pub fn send<
+        O: Send + 'static,
+        M: FnOnce(Result<steamworks::QueryResults<'_>, SteamError>) -> O + Send + 'static,
+    >(
+        self,
+        mutate: M,
+    ) -> Result<impl Future<Output = Result<O, OneShotRecvError>>, WorkshopQueryError> {
+        let mut query = self
+            .client
+            .ugc()
+            .query_all(
+                self.query_type,
+                self.ugc_type,
+                self.app_ids,
+                self.page_number,
+            )?
+            .include_long_desc(self.return_long_description)
+            .include_metadata(self.return_metadata)
+            .include_key_value_tags(self.return_key_value_tags);
+
+        if let Some(search_text) = self.search_text.as_ref() {
+            query = query.set_search_text(search_text);
+        }
+        for tag in self.required_tags.iter() {
+            query = query.require_tag(tag);
+        }
+        for tag in self.excluded_tags.iter() {
+            query = query.exclude_tag(tag);
+        }
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        query.fetch(move |res| {
+            // Don't really care if reciever is dropped...
+            let _ = tx.send(mutate(res)).is_ok();
+        });
+
+        Ok(async { rx.await })
+    }
+}
+
+/// Subscribes to a workshop item. Steam downloads it in the background.
+pub fn subscribe_item(
+    client: &steamworks::Client,
+    published_file_id: PublishedFileId,
+) -> impl Future<Output = Result<Result<(), SteamError>, OneShotRecvError>> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    client.ugc().subscribe_item(published_file_id, move |res| {
+        let _ = tx.send(res).is_ok();
+    });
+
+    async { rx.await }
+}
+
+/// Unsubscribes from a workshop item. Steam removes it once the game exits.
+pub fn unsubscribe_item(
+    client: &steamworks::Client,
+    published_file_id: PublishedFileId,
+) -> impl Future<Output = Result<Result<(), SteamError>, OneShotRecvError>> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    client
+        .ugc()
+        .unsubscribe_item(published_file_id, move |res| {
+            let _ = tx.send(res).is_ok();
+        });
+
+    async { rx.await }
+}
+
+/// The first callback id of ISteamUGC.
+///
+/// The ids below are offsets from it, taken from the k_iCallback of each struct in isteamugc.h of Steamworks SDK 1.48,
+/// which steamworks-sys is built against at the pinned rev. Steam keeps callback ids stable across SDK versions.
+const STEAM_UGC_CALLBACKS: i32 = sys::k_iSteamUGCCallbacks as i32;
+
+/// The callback id of SetUserItemVoteResult_t.
+const SET_USER_ITEM_VOTE_RESULT_ID: i32 = STEAM_UGC_CALLBACKS + 8;
+
+/// The callback id of UserFavoriteItemsListChanged_t.
+const USER_FAVORITE_ITEMS_LIST_CHANGED_ID: i32 = STEAM_UGC_CALLBACKS + 7;
+
+/// The callback id of ItemInstalled_t.
+const ITEM_INSTALLED_ID: i32 = STEAM_UGC_CALLBACKS + 5;
+
+/// The callback id of DownloadItemResult_t.
+const DOWNLOAD_ITEM_RESULT_ID: i32 = STEAM_UGC_CALLBACKS + 6;
+
+/// Sent when a workshop item is installed or updated, like after subscribing to it.
+#[derive(Debug, Clone, Copy)]
+pub struct ItemInstalled {
+    pub app_id: AppId,
+    pub published_file_id: PublishedFileId,
+}
+
+unsafe impl steamworks::Callback for ItemInstalled {
+    const ID: i32 = ITEM_INSTALLED_ID;
+    const SIZE: i32 = std::mem::size_of::<sys::ItemInstalled_t>() as i32;
+
+    unsafe fn from_raw(raw: *mut c_void) -> Self {
+        let result = &*(raw as *const sys::ItemInstalled_t);
+        Self {
+            app_id: AppId(result.m_unAppID),
+            published_file_id: PublishedFileId(result.m_nPublishedFileId),
+        }
+    }
+}
+
+/// Sent when a download started with download_item finishes.
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadItemResult {
+    pub app_id: AppId,
+    pub published_file_id: PublishedFileId,
+    pub error: Option<SteamError>,
+}
+
+unsafe impl steamworks::Callback for DownloadItemResult {
+    const ID: i32 = DOWNLOAD_ITEM_RESULT_ID;
+    const SIZE: i32 = std::mem::size_of::<sys::DownloadItemResult_t>() as i32;
+
+    unsafe fn from_raw(raw: *mut c_void) -> Self {
+        let result = &*(raw as *const sys::DownloadItemResult_t);
+        Self {
+            app_id: AppId(result.m_unAppID),
+            published_file_id: PublishedFileId(result.m_nPublishedFileId),
+            error: match result.m_eResult {
+                sys::EResult::k_EResultOK => None,
+                e => Some(e.into()),
+            },
+        }
+    }
+}
+
+/// A raw api call that is waiting for its result.
+struct PendingCallResult {
+    api_call: sys::SteamAPICall_t,
+    complete: Box<dyn FnOnce(*mut sys::ISteamUtils, sys::SteamAPICall_t) + Send>,
+}
+
+/// Polls the results of ugc api calls that steamworks does not wrap, which are voting and favorites.
+///
+/// Calls only complete if poll is called regularly, like steam callbacks.
+/// Clones share the same pending calls, so calls can be made from any thread.
+#[derive(Clone)]
+pub struct CallResultPoller {
+    client: steamworks::Client,
+    pending: Arc<Mutex<Vec<PendingCallResult>>>,
+}
+
+impl CallResultPoller {
+    /// Makes a new CallResultPoller.
+    pub fn new(client: &steamworks::Client) -> Self {
+        Self {
+            client: client.clone(),
+            pending: Arc::default(),
+        }
+    }
+
+    /// Completes the calls whose results are ready.
+    pub fn poll(&self) {
+        let utils = unsafe { get_steam_utils() };
+
+        let completed: Vec<PendingCallResult> = {
+            let mut pending = self.pending.lock();
+            let mut completed = Vec::new();
+            let mut i = 0;
+            while i < pending.len() {
+                let mut failed = false;
+                let is_completed = unsafe {
+                    sys::SteamAPI_ISteamUtils_IsAPICallCompleted(
+                        utils,
+                        pending[i].api_call,
+                        &mut failed,
+                    )
+                };
+
+                if is_completed {
+                    completed.push(pending.swap_remove(i));
+                } else {
+                    i += 1;
+                }
+            }
+            completed
+        };
+
+        // Don't hold the lock while completing, as that may start new calls.
+        for pending in completed {
+            (pending.complete)(utils, pending.api_call);
+        }
+    }
+
+    /// Waits for the result of an api call, getting its EResult and output with get_result.
+    fn register<T: 'static, O: Send + 'static>(
+        &self,
+        api_call: sys::SteamAPICall_t,
+        callback_id: i32,
+        get_result: fn(&T) -> (sys::EResult, O),
+    ) -> impl Future<Output = Result<Result<O, SteamError>, OneShotRecvError>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        if api_call == sys::k_uAPICallInvalid {
+            let _ = tx.send(Err(SteamError::InvalidParameter)).is_ok();
+        } else {
+            self.pending.lock().push(PendingCallResult {
+                api_call,
+                complete: Box::new(move |utils, api_call| {
+                    let res = unsafe { get_call_result::<T>(utils, api_call, callback_id) }
+                        .and_then(|result| match get_result(&result) {
+                            (sys::EResult::k_EResultOK, output) => Ok(output),
+                            (e, _) => Err(e.into()),
+                        });
+                    let _ = tx.send(res).is_ok();
+                }),
+            });
+        }
+
+        async { rx.await }
+    }
+
+    /// Votes an item up or down.
+    pub fn set_user_item_vote(
+        &self,
+        published_file_id: PublishedFileId,
+        vote_up: bool,
+    ) -> impl Future<Output = Result<Result<(), SteamError>, OneShotRecvError>> {
+        let api_call = unsafe {
+            sys::SteamAPI_ISteamUGC_SetUserItemVote(get_steam_ugc(), published_file_id.0, vote_up)
+        };
+
+        self.register(
+            api_call,
+            SET_USER_ITEM_VOTE_RESULT_ID,
+            |result: &sys::SetUserItemVoteResult_t| (result.m_eResult, ()),
+        )
+    }
+
+    /// Adds an item to the user's favorites, or removes it if favorite is false.
+    pub fn set_item_favorite(
+        &self,
+        published_file_id: PublishedFileId,
+        favorite: bool,
+    ) -> impl Future<Output = Result<Result<(), SteamError>, OneShotRecvError>> {
+        let app_id = self.client.utils().app_id().0;
+        let api_call = unsafe {
+            let ugc = get_steam_ugc();
+            if favorite {
+                sys::SteamAPI_ISteamUGC_AddItemToFavorites(ugc, app_id, published_file_id.0)
+            } else {
+                sys::SteamAPI_ISteamUGC_RemoveItemFromFavorites(ugc, app_id, published_file_id.0)
+            }
+        };
+
+        self.register(
+            api_call,
+            USER_FAVORITE_ITEMS_LIST_CHANGED_ID,
+            |result: &sys::UserFavoriteItemsListChanged_t| (result.m_eResult, ()),
+        )
+    }
+}
+
+/// Gets the ISteamUGC interface of the version steamworks-sys was generated from,
+/// so the vtable matches the bindings even if the pinned rev changes.
+///
+/// # Safety
+/// Steam must be initialized.
+unsafe fn get_steam_ugc() -> *mut sys::ISteamUGC {
+    sys::SteamInternal_FindOrCreateUserInterface(
+        sys::SteamAPI_GetHSteamUser(),
+        sys::STEAMUGC_INTERFACE_VERSION.as_ptr().cast(),
+    )
+    .cast()
+}
+
+/// Gets the ISteamUtils interface of the version steamworks-sys was generated from.
+///
+/// # Safety
+/// Steam must be initialized.
+unsafe fn get_steam_utils() -> *mut sys::ISteamUtils {
+    // Utils is not tied to a user, so it is looked up with user 0 like the SDK's SteamUtils().
+    sys::SteamInternal_FindOrCreateUserInterface(
+        0,
+        sys::STEAMUTILS_INTERFACE_VERSION.as_ptr().cast(),
+    )
+    .cast()
+}
+
+/// Copies out the result of a completed api call.
+///
+/// # Safety
+/// T must be the result struct with the given callback id.
+unsafe fn get_call_result<T>(
+    utils: *mut sys::ISteamUtils,
+    api_call: sys::SteamAPICall_t,
+    callback_id: i32,
+) -> Result<T, SteamError> {
+    let mut result = MaybeUninit::<T>::uninit();
+    let mut failed = false;
+    let ok = sys::SteamAPI_ISteamUtils_GetAPICallResult(
+        utils,
+        api_call,
+        result.as_mut_ptr().cast(),
+        std::mem::size_of::<T>() as i32,
+        callback_id,
+        &mut failed,
+    );
+
+    if ok && !failed {
+        Ok(result.assume_init())
+    } else {
+        Err(SteamError::IOFailure)
+    }
+}
+
+/// Creates a new, empty community item for the current app.
+/// It has no content until an update is submitted with an ItemUpdateBuilder.
+pub fn create_item(
+    client: &steamworks::Client,
+) -> impl Future<Output = Result<Result<CreatedItem, SteamError>, OneShotRecvError>> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    client
+        .ugc()
+        .create_item(client.utils().app_id(), FileType::Community, move |res| {
+            let res = res.map(
+                |(published_file_id, needs_to_accept_agreement)| CreatedItem {
+                    published_file_id,
+                    needs_to_accept_agreement,
+                },
+            );
+            let _ = tx.send(res).is_ok();
+        });
+
+    async { rx.await }
+}
+
+/// A workshop item made with create_item.
+#[derive(Debug, Clone, Copy)]
+pub struct CreatedItem {
+    pub published_file_id: PublishedFileId,
+
+    /// If true, the item stays hidden until the user accepts the workshop legal agreement.
+    pub needs_to_accept_agreement: bool,
+}
+
+#[derive(Debug)]
+pub enum ItemUpdateError {
+    /// A string field contains a nul byte, which steam can't store
+    Nul(&'static str),
+
+    /// A path does not exist, so steam would reject it
+    MissingPath(&'static str),
+}
+
+impl std::fmt::Display for ItemUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nul(field) => write!(f, "The item's {} contains a nul byte", field),
+            Self::MissingPath(field) => write!(f, "The item's {} path does not exist", field),
+        }
+    }
+}
+
+impl StdError for ItemUpdateError {}
+
+/// Who can see a workshop item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemVisibility {
+    Public,
+    FriendsOnly,
+    Private,
+    Unlisted,
+}
+
+impl ItemVisibility {
+    /// All visibilities, in the order they are shown in the ui.
+    pub const ALL: [Self; 4] = [
+        Self::Public,
+        Self::FriendsOnly,
+        Self::Private,
+        Self::Unlisted,
+    ];
+
+    /// Gets a user-facing name for this visibility.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Public => "Public",
+            Self::FriendsOnly => "Friends Only",
+            Self::Private => "Private",
+            Self::Unlisted => "Unlisted",
+        }
+    }
+
+    fn to_steam(self) -> PublishedFileVisibility {
+        match self {
+            Self::Public => PublishedFileVisibility::Public,
+            Self::FriendsOnly => PublishedFileVisibility::FriendsOnly,
+            Self::Private => PublishedFileVisibility::Private,
+            Self::Unlisted => PublishedFileVisibility::Unlisted,
+        }
+    }
+}
+
+/// What an item update is currently doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemUpdateStatus {
+    /// The update is not running, it either finished or failed.
+    Invalid,
+    PreparingConfig,
+    PreparingContent,
+    UploadingContent,
+    UploadingPreviewFile,
+    CommittingChanges,
+}
+
+impl ItemUpdateStatus {
+    /// Gets a user-facing name for this status.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Invalid => "Waiting",
+            Self::PreparingConfig => "Preparing config",
+            Self::PreparingContent => "Preparing content",
+            Self::UploadingContent => "Uploading content",
+            Self::UploadingPreviewFile => "Uploading preview",
+            Self::CommittingChanges => "Committing changes",
+        }
+    }
+}
+
+impl From<UpdateStatus> for ItemUpdateStatus {
+    fn from(status: UpdateStatus) -> Self {
+        match status {
+            UpdateStatus::Invalid => Self::Invalid,
+            UpdateStatus::PreparingConfig => Self::PreparingConfig,
+            UpdateStatus::PreparingContent => Self::PreparingContent,
+            UpdateStatus::UploadingContent => Self::UploadingContent,
+            UpdateStatus::UploadingPreviewFile => Self::UploadingPreviewFile,
+            UpdateStatus::CommittingChanges => Self::CommittingChanges,
+        }
+    }
+}
+
+/// Reports the progress of a submitted item update.
+pub struct ItemUpdateWatcher {
+    handle: UpdateWatchHandle<ClientManager>,
+}
+
+impl ItemUpdateWatcher {
+    /// Gets the status of the update, and the bytes processed and total for the current step.
+    pub fn progress(&self) -> (ItemUpdateStatus, u64, u64) {
+        let (status, processed, total) = self.handle.progress();
+        (status.into(), processed, total)
+    }
+}
+
+impl std::fmt::Debug for ItemUpdateWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ItemUpdateWatcher")
+            .field("progress", &self.progress())
+            .finish()
+    }
+}
+
+/// Builds and submits an update to a workshop item. Only the fields that are set are changed.
+pub struct ItemUpdateBuilder<'a> {
+    client: &'a steamworks::Client,
+    published_file_id: PublishedFileId,
+    title: Option<String>,
+    description: Option<String>,
+    tags: Option<Vec<String>>,
+    visibility: Option<ItemVisibility>,
+    content_path: Option<PathBuf>,
+    preview_path: Option<PathBuf>,
+    change_note: Option<String>,
+}
+
+impl<'a> ItemUpdateBuilder<'a> {
+    /// Creates a new ItemUpdateBuilder for the given item.
+    pub fn new(client: &'a steamworks::Client, published_file_id: PublishedFileId) -> Self {
+        Self {
+            client,
+            published_file_id,
+            title: None,
+            description: None,
+            tags: None,
+            visibility: None,
+            content_path: None,
+            preview_path: None,
+            change_note: None,
+        }
+    }
+
+    /// Sets the title.
+    pub fn title(mut self, title: String) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    /// Sets the description.
+    pub fn description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    /// Sets the tags, replacing the old ones.
+    pub fn tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    /// Sets the visibility.
+    pub fn visibility(mut self, visibility: ItemVisibility) -> Self {
+        self.visibility = Some(visibility);
+        self
+    }
+
+    /// Sets the folder that is uploaded as the item's content.
+    pub fn content_path(mut self, content_path: PathBuf) -> Self {
+        self.content_path = Some(content_path);
+        self
+    }
+
+    /// Sets the image file that is uploaded as the item's preview.
+    pub fn preview_path(mut self, preview_path: PathBuf) -> Self {
+        self.preview_path = Some(preview_path);
+        self
+    }
+
+    /// Sets the change note shown in the item's history.
+    pub fn change_note(mut self, change_note: String) -> Self {
+        self.change_note = Some(change_note);
+        self
+    }
+
+    /// Checks the fields steamworks would panic on if steam rejected them.
+    fn validate(&self) -> Result<(), ItemUpdateError> {
+        let strings = [
+            ("title", self.title.as_deref()),
+            ("description", self.description.as_deref()),
+            ("change note", self.change_note.as_deref()),
+        ];
+        for (field, value) in strings.iter() {
+            if value.map_or(false, |value| value.contains('\0')) {
+                return Err(ItemUpdateError::Nul(*field));
+            }
+        }
+        let has_nul_tag = self.tags.iter().flatten().any(|tag| tag.contains('\0'));
+        if has_nul_tag {
+            return Err(ItemUpdateError::Nul("tags"));
+        }
+
+        if self
+            .content_path
+            .as_ref()
+            .map_or(false, |path| !path.is_dir())
+        {
+            return Err(ItemUpdateError::MissingPath("content"));
+        }
+        if self
+            .preview_path
+            .as_ref()
+            .map_or(false, |path| !path.is_file())
+        {
+            return Err(ItemUpdateError::MissingPath("preview"));
+        }
+
+        Ok(())
+    }
+
+    /// Submits the update.
+    /// Returns a watcher for the upload progress and a future that resolves to whether the user still needs to accept the workshop legal agreement.
+    pub fn send(
+        self,
+    ) -> Result<
+        (
+            ItemUpdateWatcher,
+            impl Future<Output = Result<Result<bool, SteamError>, OneShotRecvError>>,
+        ),
+        ItemUpdateError,
+    > {
+        self.validate()?;
+
+        let mut handle = self
+            .client
+            .ugc()
+            .start_item_update(self.client.utils().app_id(), self.published_file_id);
+        if let Some(title) = self.title.as_deref() {
+            handle = handle.title(title);
+        }
+        if let Some(description) = self.description.as_deref() {
+            handle = handle.description(description);
+        }
+        if let Some(tags) = self.tags {
+            handle = handle.tags(tags);
+        }
+        if let Some(visibility) = self.visibility {
+            handle = handle.visibility(visibility.to_steam());
+        }
+        if let Some(content_path) = self.content_path.as_deref() {
+            handle = handle.content_path(content_path);
+        }
+        if let Some(preview_path) = self.preview_path.as_deref() {
+            handle = handle.preview_path(preview_path);
+        }
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let handle = handle.submit(self.change_note.as_deref(), move |res| {
+            let res = res.map(|(_, needs_to_accept_agreement)| needs_to_accept_agreement);
+            let _ = tx.send(res).is_ok();
+        });
+
+        Ok((ItemUpdateWatcher { handle }, async { rx.await }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Retries a query whose fetches give the scripted results, returning the result and the number of fetches.
+    fn run_scripted_query(
+        max_attempts: u32,
+        results: Vec<Result<u32, SteamError>>,
+    ) -> (Result<u32, SteamError>, usize) {
+        let retry_policy = RetryPolicy {
+            max_attempts,
+            initial_delay_ms: 1,
+            ..RetryPolicy::default()
+        };
+        let mut results: VecDeque<_> = results.into();
+        let mut fetches = 0;
+        let mut fetch = || {
+            fetches += 1;
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            let _ = tx.send(results.pop_front().expect("fetched too many times"));
+            Ok::<_, String>(rx)
+        };
+
+        let rx = fetch().expect("failed to fetch");
+        let mut tokio_rt = tokio::runtime::Runtime::new().expect("failed to start tokio");
+        let result = tokio_rt
+            .block_on(retry_query(retry_policy, rx, fetch))
+            .expect("query was dropped");
+        (result, fetches)
+    }
+
+    #[test]
+    fn retry_query_retries_then_succeeds() {
+        let (result, fetches) = run_scripted_query(
+            3,
+            vec![Err(SteamError::Timeout), Err(SteamError::Busy), Ok(1)],
+        );
+        assert!(matches!(result, Ok(1)));
+        assert_eq!(fetches, 3);
+    }
+
+    #[test]
+    fn retry_query_stops_on_non_retryable() {
+        let (result, fetches) = run_scripted_query(3, vec![Err(SteamError::AccessDenied), Ok(1)]);
+        assert!(matches!(result, Err(SteamError::AccessDenied)));
+        assert_eq!(fetches, 1);
+    }
+
+    #[test]
+    fn retry_query_stops_at_max_attempts() {
+        let (result, fetches) = run_scripted_query(
+            2,
+            vec![
+                Err(SteamError::Timeout),
+                Err(SteamError::ServiceUnavailable),
+                Ok(1),
+            ],
+        );
+        assert!(matches!(result, Err(SteamError::ServiceUnavailable)));
+        assert_eq!(fetches, 2);
+    }
 }
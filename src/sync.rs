@@ -0,0 +1,1848 @@
+use crate::{
+    config::{
+        SyncConfig,
+        SyncList,
+    },
+    level::Level,
+    pack::get_imported_dir,
+    retry::{
+        RetryPolicy,
+        Retryable,
+        RetryableError,
+    },
+    steamworks_util::{
+        collect_query_results,
+        OneShotRecvError,
+        QueryResultEntry,
+        UgcQueryBuilder,
+        WorkshopQueryError,
+    },
+    util::{
+        get_checksum,
+        sanitize_file_name,
+    },
+    workshop::{
+        WorkshopCache,
+        WorkshopItem,
+    },
+};
+use log::{
+    debug,
+    error,
+    info,
+    warn,
+};
+use parking_lot::Mutex;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    error::Error as StdError,
+    future::Future,
+    path::{
+        Component,
+        Path,
+        PathBuf,
+    },
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        Arc,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use tokio::{
+    runtime::Runtime as TokioRuntime,
+    sync::Semaphore,
+    task::JoinError,
+};
+
+#[derive(Debug)]
+pub enum SteamWorkshopSyncError {
+    Recieve(OneShotRecvError),
+    Steam(steamworks::SteamError),
+    Io(std::io::Error),
+    Join(JoinError),
+
+    InvalidSyncDir,
+}
+
+impl From<OneShotRecvError> for SteamWorkshopSyncError {
+    fn from(e: OneShotRecvError) -> Self {
+        Self::Recieve(e)
+    }
+}
+
+impl From<steamworks::SteamError> for SteamWorkshopSyncError {
+    fn from(e: steamworks::SteamError) -> Self {
+        Self::Steam(e)
+    }
+}
+
+impl From<std::io::Error> for SteamWorkshopSyncError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<JoinError> for SteamWorkshopSyncError {
+    fn from(e: JoinError) -> Self {
+        Self::Join(e)
+    }
+}
+
+impl std::fmt::Display for SteamWorkshopSyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Recieve(e) => e.fmt(f),
+            Self::Steam(e) => e.fmt(f),
+            Self::Io(e) => e.fmt(f),
+            Self::Join(e) => e.fmt(f),
+
+            Self::InvalidSyncDir => write!(f, "The sync dir is invalid"),
+        }
+    }
+}
+
+impl StdError for SteamWorkshopSyncError {}
+
+impl Retryable for SteamWorkshopSyncError {
+    fn retryable_kind(&self) -> Option<RetryableError> {
+        match self {
+            Self::Steam(e) => e.retryable_kind(),
+            Self::Io(e) => e.retryable_kind(),
+            _ => None,
+        }
+    }
+}
+
+/// A record of the files written by the last successful sync.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SyncManifest {
+    pub items: Vec<SyncManifestItem>,
+
+    /// Items that were not synced because they are not valid levels.
+    #[serde(default)]
+    pub quarantined: Vec<QuarantinedItem>,
+
+    /// Non-level files copied along with the levels of items installed as a folder.
+    #[serde(default)]
+    pub assets: Vec<SyncManifestAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SyncManifestItem {
+    pub published_file_id: u64,
+    pub title: String,
+    pub time_updated: u32,
+    pub path: PathBuf,
+
+    /// The level file this was copied from, relative to the item's install folder.
+    /// None if the item was installed as a single file.
+    #[serde(default)]
+    pub source: Option<PathBuf>,
+
+    /// The sha256 of the file when it was synced, as hex.
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SyncManifestAsset {
+    pub published_file_id: u64,
+
+    /// The file this was copied from, relative to the item's install folder.
+    pub source: PathBuf,
+    pub path: PathBuf,
+
+    /// The sha256 of the file when it was synced, as hex.
+    #[serde(default)]
+    pub checksum: Option<String>,
+}
+
+impl SyncManifest {
+    /// Loads a manifest, returning an empty one if it does not exist or is invalid.
+    pub async fn load(path: &Path) -> Self {
+        match tokio::fs::read(path).await {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                warn!("Invalid sync manifest '{}': {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Saves the manifest, creating its dir if needed.
+    pub async fn save(&self, path: &Path) -> Result<(), std::io::Error> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        tokio::fs::write(path, data).await
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct QuarantinedItem {
+    pub published_file_id: u64,
+    pub title: String,
+
+    /// The copy of the item in the quarantine dir.
+    pub path: PathBuf,
+    pub reason: String,
+
+    /// The level file this was copied from, relative to the item's install folder.
+    #[serde(default)]
+    pub source: Option<PathBuf>,
+}
+
+/// Gets the dir invalid items are copied to instead of the sync dir.
+pub fn get_quarantine_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("quarantine")
+}
+
+/// Checks that an item's content is a playable level, returning a readable reason if not.
+fn check_level(data: &[u8]) -> Result<(), String> {
+    Level::parse_bytes(data)
+        .and_then(|level| level.validate())
+        .map_err(|e| e.to_string())
+}
+
+/// Writes an invalid level and why it is invalid to the quarantine dir, returning the path of the level.
+async fn quarantine_level(
+    data_dir: &Path,
+    name: &str,
+    data: &[u8],
+    reason: &str,
+) -> Result<PathBuf, std::io::Error> {
+    let quarantine_dir = get_quarantine_dir(data_dir);
+    tokio::fs::create_dir_all(&quarantine_dir).await?;
+    let path = quarantine_dir.join(format!("{}.txt", name));
+    tokio::fs::write(&path, data).await?;
+    tokio::fs::write(quarantine_dir.join(format!("{}.reason.txt", name)), reason).await?;
+
+    Ok(path)
+}
+
+/// How a workshop item's content is laid out where steam installed it.
+#[derive(Debug)]
+enum ItemLayout {
+    /// The install path is the level file itself.
+    File,
+
+    /// The install path is a dir. The paths are relative to it, in sorted order.
+    Dir {
+        levels: Vec<PathBuf>,
+        assets: Vec<PathBuf>,
+    },
+}
+
+impl ItemLayout {
+    /// Detects the layout of an item's install path.
+    ///
+    /// In a dir, `.txt` files are levels and everything else is an asset.
+    /// A dir holding a single file and no `.txt` files is treated as holding one level.
+    async fn detect(install_path: &Path) -> Result<Self, std::io::Error> {
+        if !tokio::fs::metadata(install_path).await?.is_dir() {
+            return Ok(Self::File);
+        }
+
+        let mut files: Vec<PathBuf> = list_files(install_path)
+            .await?
+            .iter()
+            .filter_map(|path| path.strip_prefix(install_path).ok())
+            .map(Path::to_path_buf)
+            .collect();
+        files.sort();
+
+        let (mut levels, mut assets): (Vec<_>, Vec<_>) = files
+            .into_iter()
+            .partition(|path| path.extension().map_or(false, |ext| ext == "txt"));
+        if levels.is_empty() && assets.len() == 1 {
+            std::mem::swap(&mut levels, &mut assets);
+        }
+
+        Ok(Self::Dir { levels, assets })
+    }
+}
+
+/// Lists the files in a dir and its subdirs.
+async fn list_files(dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Gets the file name a level of an item is synced to, given the item's sanitized name.
+///
+/// Packs with more than one level get one file per level, named after the item and the level's path in the pack.
+fn get_level_file_name(name: &str, source: Option<&Path>, is_pack: bool) -> PathBuf {
+    let mut name = name.to_string();
+    if let Some(source) = source.filter(|_| is_pack) {
+        for component in source.with_extension("").components() {
+            name.push_str(" - ");
+            name.push_str(&sanitize_file_name(
+                &component.as_os_str().to_string_lossy(),
+            ));
+        }
+    }
+
+    PathBuf::from(format!("{}.txt", name))
+}
+
+/// Gets a file name for an item from its title that no other item in the same list dir uses.
+/// The published file id is added to the name if it is taken.
+fn get_item_name(
+    item_copies: &[ItemCopy],
+    list_dir: &Path,
+    title: &str,
+    published_file_id: u64,
+) -> String {
+    let name = sanitize_file_name(title);
+
+    // Compare case-insensitively, as the sync dir may be on a case-insensitive file system.
+    let is_taken = item_copies.iter().any(|item_copy| {
+        item_copy.list_dir == list_dir && item_copy.name.to_lowercase() == name.to_lowercase()
+    });
+    if is_taken {
+        format!("{} ({})", name, published_file_id)
+    } else {
+        name
+    }
+}
+
+/// Returns true if the path is inside the dir.
+///
+/// Paths with `..` in them are never inside, as Path::starts_with compares components without resolving them.
+fn is_in_dir(path: &Path, dir: &Path) -> bool {
+    path != dir
+        && path.starts_with(dir)
+        && !path
+            .components()
+            .any(|component| component == Component::ParentDir)
+}
+
+/// Removes a synced file, ignoring it if it is already gone.
+///
+/// Dirs left empty between the file and the sync dir are removed too.
+async fn remove_synced_file(sync_dir: &Path, path: &Path) -> Result<(), std::io::Error> {
+    // The manifest may have been edited, so don't trust its paths.
+    if !is_in_dir(path, sync_dir) {
+        warn!(
+            "Not removing '{}', as it is not in the sync dir",
+            path.display()
+        );
+        return Ok(());
+    }
+
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let mut dir = path.parent();
+    while let Some(parent) = dir.filter(|dir| dir.starts_with(sync_dir) && *dir != sync_dir) {
+        // This fails if the dir is not empty, which means it is still in use.
+        if tokio::fs::remove_dir(parent).await.is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+
+    Ok(())
+}
+
+/// Gets the path of the sync manifest in the data dir.
+pub fn get_sync_manifest_path(data_dir: &Path) -> PathBuf {
+    data_dir.join("sync_manifest.json")
+}
+
+#[derive(Debug)]
+pub enum SteamWorkshopSyncState {
+    /// Steam could not be initialized, so only previously synced levels are available.
+    Offline,
+
+    Starting,
+
+    InProgress {
+        synced: usize,
+        total: usize,
+        bytes_synced: u64,
+
+        /// The size of the installed items as reported by steam, which the copied bytes should add up to.
+        total_bytes: u64,
+
+        /// The title of the item that was started last.
+        current_title: Option<String>,
+        started: Instant,
+    },
+
+    Done(usize),
+    Failed(SteamWorkshopSyncError),
+}
+
+impl SteamWorkshopSyncState {
+    pub fn begin_sync(&mut self, len: usize, total_bytes: u64) {
+        *self = Self::InProgress {
+            synced: 0,
+            total: len,
+            bytes_synced: 0,
+            total_bytes,
+            current_title: None,
+            started: Instant::now(),
+        };
+    }
+
+    pub fn add_synced(&mut self, n: usize) {
+        if let Self::InProgress { synced, .. } = self {
+            *synced += n;
+        }
+    }
+
+    pub fn add_bytes(&mut self, n: u64) {
+        if let Self::InProgress { bytes_synced, .. } = self {
+            *bytes_synced += n;
+        }
+    }
+
+    /// Takes back bytes that were counted by a failed copy, so they are not counted twice when it is retried.
+    pub fn remove_bytes(&mut self, n: u64) {
+        if let Self::InProgress { bytes_synced, .. } = self {
+            *bytes_synced = bytes_synced.saturating_sub(n);
+        }
+    }
+
+    pub fn set_current_title(&mut self, title: String) {
+        if let Self::InProgress { current_title, .. } = self {
+            *current_title = Some(title);
+        }
+    }
+
+    /// Estimates the time left from the rate bytes were copied at so far.
+    pub fn get_eta(&self) -> Option<Duration> {
+        match self {
+            Self::InProgress {
+                bytes_synced,
+                total_bytes,
+                started,
+                ..
+            } if *bytes_synced > 0 => {
+                let bytes_left = total_bytes.saturating_sub(*bytes_synced);
+                let secs_per_byte = started.elapsed().as_secs_f64() / *bytes_synced as f64;
+                Some(Duration::from_secs_f64(secs_per_byte * bytes_left as f64))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn finish_sync(&mut self) {
+        if let Self::InProgress { synced, .. } = *self {
+            *self = Self::Done(synced);
+        }
+    }
+
+    pub fn set_fail(&mut self, e: SteamWorkshopSyncError) {
+        *self = Self::Failed(e);
+    }
+
+    pub fn is_syncing(&self) -> bool {
+        matches!(self, Self::Starting | Self::InProgress { .. })
+    }
+}
+
+/// Creates the sync dir if it does not exist, and makes sure it is a dir.
+pub fn ensure_sync_dir(sync_dir: &Path) -> Result<(), SteamWorkshopSyncError> {
+    if !sync_dir.exists() {
+        std::fs::create_dir_all(sync_dir)?;
+    }
+
+    if !sync_dir.is_dir() {
+        return Err(SteamWorkshopSyncError::InvalidSyncDir);
+    }
+
+    Ok(())
+}
+
+/// A queried item along with the lists it was found in, in config order.
+struct SyncEntry<T = QueryResultEntry> {
+    entry: T,
+    lists: Vec<SyncList>,
+}
+
+/// Merges the results of the list queries, de-duplicating items by published file id.
+/// Items are kept in the order they were first found in.
+fn merge_list_results<T, F>(
+    list_results: Vec<(SyncList, Vec<T>)>,
+    get_published_file_id: F,
+) -> Vec<SyncEntry<T>>
+where
+    F: Fn(&T) -> u64,
+{
+    let mut sync_entries: Vec<SyncEntry<T>> = Vec::new();
+    for (sync_list, entries) in list_results {
+        for entry in entries {
+            let published_file_id = get_published_file_id(&entry);
+            match sync_entries
+                .iter_mut()
+                .find(|sync_entry| get_published_file_id(&sync_entry.entry) == published_file_id)
+            {
+                Some(sync_entry) => sync_entry.lists.push(sync_list),
+                None => sync_entries.push(SyncEntry {
+                    entry,
+                    lists: vec![sync_list],
+                }),
+            }
+        }
+    }
+    sync_entries
+}
+
+/// Queries the given user lists and syncs their items to subfolders of the sync dir in the background.
+/// The metadata of the queried items is stored in workshop_items and cached in the data dir,
+/// and a manifest of the synced files is written to the data dir once the sync is done.
+///
+/// The queries only complete if steam callbacks are run.
+pub fn spawn_steam_workshop_sync(
+    tokio_rt: &TokioRuntime,
+    steam_client: &steamworks::Client,
+    sync_dir: PathBuf,
+    data_dir: PathBuf,
+    sync_lists: &[SyncList],
+    sync_config: &SyncConfig,
+    workshop_items: Arc<Mutex<Vec<WorkshopItem>>>,
+) -> Result<Arc<Mutex<SteamWorkshopSyncState>>, WorkshopQueryError> {
+    let mut queried_lists: Vec<SyncList> = Vec::with_capacity(sync_lists.len());
+    for sync_list in sync_lists.iter() {
+        if !queried_lists.contains(sync_list) {
+            queried_lists.push(*sync_list);
+        }
+    }
+
+    let ugc_query_futures = queried_lists
+        .iter()
+        .map(|sync_list| {
+            info!("Querying {} workshop items", sync_list.as_str());
+            let ugc_query_future = UgcQueryBuilder::new(steam_client)
+                .user_list(sync_list.user_list())
+                .send_with_retry(sync_config.retry.clone(), collect_query_results)?;
+            Ok((*sync_list, ugc_query_future))
+        })
+        .collect::<Result<Vec<_>, WorkshopQueryError>>()?;
+
+    let steam_workshop_sync_state = Arc::new(Mutex::new(SteamWorkshopSyncState::Starting));
+    let steam_workshop_sync_state_clone = steam_workshop_sync_state.clone();
+
+    let steam_client_clone = steam_client.clone();
+    let concurrency = sync_config.concurrency;
+    let retry_policy = sync_config.retry.clone();
+
+    tokio_rt.spawn(async move {
+        if let Err(e) = sync_steam_workshop(
+            steam_client_clone,
+            ugc_query_futures,
+            steam_workshop_sync_state_clone.clone(),
+            sync_dir,
+            data_dir,
+            concurrency,
+            retry_policy,
+            workshop_items,
+        )
+        .await
+        {
+            error!("Sync Failed: {}", e);
+            steam_workshop_sync_state_clone.lock().set_fail(e);
+        }
+    });
+
+    Ok(steam_workshop_sync_state)
+}
+
+async fn sync_steam_workshop<F>(
+    steam_client: steamworks::Client,
+    ugc_query_futures: Vec<(SyncList, F)>,
+    steam_workshop_sync_state: Arc<Mutex<SteamWorkshopSyncState>>,
+    sync_dir: PathBuf,
+    data_dir: PathBuf,
+    concurrency: usize,
+    retry_policy: RetryPolicy,
+    workshop_items: Arc<Mutex<Vec<WorkshopItem>>>,
+) -> Result<(), SteamWorkshopSyncError>
+where
+    F: Future<
+        Output = Result<Result<Vec<QueryResultEntry>, steamworks::SteamError>, OneShotRecvError>,
+    >,
+{
+    let queried_lists: Vec<SyncList> = ugc_query_futures
+        .iter()
+        .map(|(sync_list, _)| *sync_list)
+        .collect();
+    let mut list_results = Vec::with_capacity(ugc_query_futures.len());
+    for (sync_list, ugc_query_future) in ugc_query_futures {
+        list_results.push((sync_list, ugc_query_future.await??));
+    }
+    let workshop_data = merge_list_results(list_results, |entry| entry.result.published_file_id.0);
+    let manifest_path = get_sync_manifest_path(&data_dir);
+
+    let friends = steam_client.friends();
+    let workshop_cache = {
+        let mut workshop_items = workshop_items.lock();
+        let mut new_workshop_items: Vec<WorkshopItem> = workshop_data
+            .iter()
+            .map(|sync_entry| {
+                let entry = &sync_entry.entry;
+                let author = friends.get_friend(entry.result.owner).name();
+                let mut item = WorkshopItem::from_query_result(
+                    &entry.result,
+                    author,
+                    entry.preview_url.clone(),
+                );
+
+                // Keep what the user did this session for state that is not part of the queried lists.
+                if let Some(old_item) = workshop_items
+                    .iter()
+                    .find(|old_item| old_item.published_file_id == item.published_file_id)
+                {
+                    item.user_state = old_item.user_state;
+                }
+                if queried_lists.contains(&SyncList::Subscribed) {
+                    item.user_state.subscribed = sync_entry.lists.contains(&SyncList::Subscribed);
+                }
+                if queried_lists.contains(&SyncList::Favorited) {
+                    item.user_state.favorited = sync_entry.lists.contains(&SyncList::Favorited);
+                }
+
+                item
+            })
+            .collect();
+
+        let workshop_cache = WorkshopCache {
+            items: new_workshop_items.clone(),
+        };
+
+        // Keep items that are gone from the query, like cached ones that were unsubscribed elsewhere,
+        // but mark them so the user knows they are out of date.
+        for old_item in workshop_items.iter() {
+            let is_missing = !new_workshop_items
+                .iter()
+                .any(|item| item.published_file_id == old_item.published_file_id);
+            if is_missing {
+                let mut old_item = old_item.clone();
+                old_item.is_stale = true;
+                new_workshop_items.push(old_item);
+            }
+        }
+        *workshop_items = new_workshop_items;
+
+        workshop_cache
+    };
+
+    if let Err(e) = workshop_cache.save(&data_dir).await {
+        warn!("Failed to save workshop cache: {}", e);
+    }
+
+    info!(
+        "Syncing {} workshop item(s) to '{}'",
+        workshop_data.len(),
+        sync_dir.display()
+    );
+
+    let old_manifest = SyncManifest::load(&manifest_path).await;
+    let mut manifest = SyncManifest::default();
+
+    // Steam is only asked about items here, the copies run in tasks limited by the semaphore.
+    let mut item_copies = Vec::with_capacity(workshop_data.len());
+    for sync_entry in workshop_data.iter() {
+        let workshop_item = &sync_entry.entry.result;
+
+        // Newly subscribed items may still be downloading, and items in other lists may not be downloaded at all.
+        let item_info = match steam_client
+            .ugc()
+            .item_install_info(workshop_item.published_file_id)
+        {
+            Some(item_info) => item_info,
+            None => {
+                warn!(
+                    "'{}' ({}) is not installed yet, skipping",
+                    workshop_item.title, workshop_item.published_file_id.0
+                );
+                steam_client
+                    .ugc()
+                    .download_item(workshop_item.published_file_id, false);
+
+                // Keep the files from the last sync until the new version is installed.
+                manifest.items.extend(
+                    old_manifest
+                        .items
+                        .iter()
+                        .filter(|item| item.published_file_id == workshop_item.published_file_id.0)
+                        .cloned(),
+                );
+                manifest.assets.extend(
+                    old_manifest
+                        .assets
+                        .iter()
+                        .filter(|asset| {
+                            asset.published_file_id == workshop_item.published_file_id.0
+                        })
+                        .cloned(),
+                );
+                continue;
+            }
+        };
+
+        // Items are only synced to the folder of the first list they are in.
+        let list_dir = sync_dir.join(sync_entry.lists[0].as_str());
+        let name = get_item_name(
+            &item_copies,
+            &list_dir,
+            &workshop_item.title,
+            workshop_item.published_file_id.0,
+        );
+        item_copies.push(ItemCopy {
+            published_file_id: workshop_item.published_file_id.0,
+            title: workshop_item.title.clone(),
+            name,
+            time_updated: workshop_item.time_updated,
+            install_path: PathBuf::from(item_info.folder),
+            size: item_info.size_on_disk,
+            list_dir,
+        });
+    }
+
+    let total_bytes = item_copies.iter().map(|item_copy| item_copy.size).sum();
+    steam_workshop_sync_state
+        .lock()
+        .begin_sync(item_copies.len(), total_bytes);
+
+    let item_outputs = copy_items(
+        item_copies,
+        &data_dir,
+        concurrency,
+        &retry_policy,
+        &steam_workshop_sync_state,
+    )
+    .await?;
+    for (published_file_id, output) in item_outputs {
+        manifest.items.extend(output.items);
+        manifest.assets.extend(output.assets);
+        manifest.quarantined.extend(output.quarantined);
+        if !output.invalid_reasons.is_empty() {
+            if let Some(item) = workshop_items
+                .lock()
+                .iter_mut()
+                .find(|item| item.published_file_id == published_file_id)
+            {
+                item.invalid_reason = Some(output.invalid_reasons.join("; "));
+            }
+        }
+    }
+
+    // Remove files of items that are no longer in any synced list.
+    for old_item in old_manifest.items.iter() {
+        let still_synced = manifest.items.iter().any(|item| item.path == old_item.path);
+        if still_synced || !is_in_dir(&old_item.path, &sync_dir) {
+            continue;
+        }
+
+        debug!(
+            "Removing '{}' ({})",
+            old_item.path.display(),
+            old_item.published_file_id
+        );
+        remove_synced_file(&sync_dir, &old_item.path).await?;
+    }
+    for old_asset in old_manifest.assets.iter() {
+        let still_synced = manifest
+            .assets
+            .iter()
+            .any(|asset| asset.path == old_asset.path);
+        if still_synced || !is_in_dir(&old_asset.path, &sync_dir) {
+            continue;
+        }
+
+        debug!(
+            "Removing asset '{}' ({})",
+            old_asset.path.display(),
+            old_asset.published_file_id
+        );
+        remove_synced_file(&sync_dir, &old_asset.path).await?;
+    }
+
+    manifest.save(&manifest_path).await?;
+
+    info!("Sync Complete");
+    steam_workshop_sync_state.lock().finish_sync();
+
+    Ok(())
+}
+
+/// An installed workshop item to copy to the sync dir.
+#[derive(Debug, Clone)]
+pub struct ItemCopy {
+    pub published_file_id: u64,
+    pub title: String,
+
+    /// The name the item's files are synced under, made from its title. It is unique in the list dir.
+    pub name: String,
+    pub time_updated: u32,
+    pub install_path: PathBuf,
+
+    /// The size of the item's install folder in bytes, as reported by steam.
+    pub size: u64,
+
+    /// The dir of the list the item is synced to.
+    pub list_dir: PathBuf,
+}
+
+/// What was written for an item by copy_item.
+#[derive(Default)]
+pub struct ItemCopyOutput {
+    items: Vec<SyncManifestItem>,
+    assets: Vec<SyncManifestAsset>,
+    quarantined: Vec<QuarantinedItem>,
+
+    /// Why levels of the item were quarantined, for showing in the level list.
+    invalid_reasons: Vec<String>,
+}
+
+/// Copies the items, running up to concurrency copies at a time and retrying failed copies with the retry policy.
+/// Returns the output of each item in the order they were given, so the manifest does not depend on which copy finished first.
+///
+/// If a copy fails, copies that have not started yet are skipped
+/// and the running ones are waited for before the error is returned.
+pub async fn copy_items(
+    item_copies: Vec<ItemCopy>,
+    data_dir: &Path,
+    concurrency: usize,
+    retry_policy: &RetryPolicy,
+    steam_workshop_sync_state: &Arc<Mutex<SteamWorkshopSyncState>>,
+) -> Result<Vec<(u64, ItemCopyOutput)>, SteamWorkshopSyncError> {
+    let data_dir = data_dir.to_path_buf();
+    let retry_policy = retry_policy.clone();
+    let steam_workshop_sync_state = steam_workshop_sync_state.clone();
+    run_item_copies(item_copies, concurrency, move |item_copy| {
+        let data_dir = data_dir.clone();
+        let retry_policy = retry_policy.clone();
+        let steam_workshop_sync_state = steam_workshop_sync_state.clone();
+        async move {
+            steam_workshop_sync_state
+                .lock()
+                .set_current_title(item_copy.title.clone());
+
+            let output = {
+                let item_copy = &item_copy;
+                let data_dir = &data_dir;
+                let steam_workshop_sync_state = &steam_workshop_sync_state;
+                copy_with_retry(
+                    &retry_policy,
+                    steam_workshop_sync_state,
+                    item_copy,
+                    move || async move {
+                        let mut bytes_read = 0;
+                        let result = copy_item(
+                            item_copy,
+                            data_dir,
+                            steam_workshop_sync_state,
+                            &mut bytes_read,
+                        )
+                        .await;
+                        (result, bytes_read)
+                    },
+                )
+                .await?
+            };
+            steam_workshop_sync_state.lock().add_synced(1);
+            Ok::<_, SteamWorkshopSyncError>(output)
+        }
+    })
+    .await
+}
+
+/// Runs copy on each item for copy_items, up to concurrency at a time.
+async fn run_item_copies<T, F, Fut>(
+    item_copies: Vec<ItemCopy>,
+    concurrency: usize,
+    copy: F,
+) -> Result<Vec<(u64, T)>, SteamWorkshopSyncError>
+where
+    T: Send + 'static,
+    F: Fn(ItemCopy) -> Fut,
+    Fut: Future<Output = Result<T, SteamWorkshopSyncError>> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let is_cancelled = Arc::new(AtomicBool::new(false));
+    let mut item_copy_handles = Vec::with_capacity(item_copies.len());
+    for item_copy in item_copies {
+        let semaphore = semaphore.clone();
+        let is_cancelled = is_cancelled.clone();
+        let published_file_id = item_copy.published_file_id;
+        let copy = copy(item_copy);
+        item_copy_handles.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            if is_cancelled.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+
+            Ok::<_, SteamWorkshopSyncError>(Some((published_file_id, copy.await?)))
+        }));
+    }
+
+    let mut item_outputs = Vec::with_capacity(item_copy_handles.len());
+    let mut item_copy_handles = item_copy_handles.into_iter();
+    while let Some(item_copy_handle) = item_copy_handles.next() {
+        match item_copy_handle.await.unwrap_or_else(|e| Err(e.into())) {
+            Ok(Some(item_output)) => item_outputs.push(item_output),
+            Ok(None) => {}
+            Err(e) => {
+                // Don't leave copies writing to the sync dir after the sync has failed.
+                is_cancelled.store(true, Ordering::SeqCst);
+                for item_copy_handle in item_copy_handles {
+                    let _ = item_copy_handle.await;
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(item_outputs)
+}
+
+/// Runs a copy of an item until it succeeds or the retry policy gives up on it.
+/// The copy returns its result and the bytes it read,
+/// which are taken back from the sync state before it is tried again so they are not counted twice.
+async fn copy_with_retry<T, F, Fut>(
+    retry_policy: &RetryPolicy,
+    steam_workshop_sync_state: &Mutex<SteamWorkshopSyncState>,
+    item_copy: &ItemCopy,
+    mut copy: F,
+) -> Result<T, SteamWorkshopSyncError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = (Result<T, SteamWorkshopSyncError>, u64)>,
+{
+    let mut attempt = 1;
+    loop {
+        let (result, bytes_read) = copy().await;
+        let error = match result {
+            Ok(output) => return Ok(output),
+            Err(e) => e,
+        };
+
+        let delay = match retry_policy.get_retry_delay(attempt, &error) {
+            Some(delay) => delay,
+            None => return Err(error),
+        };
+        warn!(
+            "Copying '{}' ({}) failed on attempt {}, retrying in {:?}: {}",
+            item_copy.title, item_copy.published_file_id, attempt, delay, error
+        );
+        steam_workshop_sync_state.lock().remove_bytes(bytes_read);
+        tokio::time::delay_for(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Copies the levels and assets of an installed item to its list dir, quarantining invalid levels.
+/// The bytes read are added to the sync state as they are copied, and to bytes_read so a failed copy can take them back.
+async fn copy_item(
+    item: &ItemCopy,
+    data_dir: &Path,
+    steam_workshop_sync_state: &Mutex<SteamWorkshopSyncState>,
+    bytes_read: &mut u64,
+) -> Result<ItemCopyOutput, SteamWorkshopSyncError> {
+    let mut output = ItemCopyOutput::default();
+    tokio::fs::create_dir_all(&item.list_dir).await?;
+
+    let install_path = item.install_path.as_path();
+    let (level_sources, asset_sources) = match ItemLayout::detect(install_path).await? {
+        ItemLayout::File => (vec![None], Vec::new()),
+        ItemLayout::Dir { levels, assets } => (levels.into_iter().map(Some).collect(), assets),
+    };
+    let is_pack = level_sources.len() > 1;
+    debug!(
+        "'{}' ({}) has {} level(s) and {} asset(s) in '{}'",
+        item.title,
+        item.published_file_id,
+        level_sources.len(),
+        asset_sources.len(),
+        install_path.display()
+    );
+
+    if level_sources.is_empty() {
+        output
+            .invalid_reasons
+            .push(String::from("The item has no level files"));
+    }
+
+    for (i, source) in level_sources.into_iter().enumerate() {
+        let source_path = match source.as_ref() {
+            Some(source) => install_path.join(source),
+            None => install_path.to_path_buf(),
+        };
+        let path = item
+            .list_dir
+            .join(get_level_file_name(&item.name, source.as_deref(), is_pack));
+        debug!(
+            "Copying '{}' ({}) from '{}' to '{}'",
+            item.title,
+            item.published_file_id,
+            source_path.display(),
+            path.display()
+        );
+
+        let data = tokio::fs::read(&source_path).await?;
+        *bytes_read += data.len() as u64;
+        steam_workshop_sync_state
+            .lock()
+            .add_bytes(data.len() as u64);
+        if let Err(reason) = check_level(&data) {
+            warn!(
+                "Quarantining '{}' ({}) from '{}': {}",
+                item.title,
+                item.published_file_id,
+                source_path.display(),
+                reason
+            );
+
+            let quarantine_name = if is_pack {
+                format!("{}_{}", item.published_file_id, i)
+            } else {
+                item.published_file_id.to_string()
+            };
+            let quarantine_path =
+                quarantine_level(data_dir, &quarantine_name, &data, &reason).await?;
+
+            output
+                .invalid_reasons
+                .push(match source.as_ref().filter(|_| is_pack) {
+                    Some(source) => format!("{}: {}", source.display(), reason),
+                    None => reason.clone(),
+                });
+            output.quarantined.push(QuarantinedItem {
+                published_file_id: item.published_file_id,
+                title: item.title.clone(),
+                path: quarantine_path,
+                reason,
+                source,
+            });
+            continue;
+        }
+
+        tokio::fs::write(&path, &data).await?;
+        output.items.push(SyncManifestItem {
+            published_file_id: item.published_file_id,
+            title: item.title.clone(),
+            time_updated: item.time_updated,
+            path,
+            source,
+            checksum: Some(get_checksum(&data)),
+        });
+    }
+
+    // Assets are copied with their layout kept, to a dir named after the item.
+    let asset_dir = item.list_dir.join(&item.name);
+    for source in asset_sources {
+        let path = asset_dir.join(&source);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let data = tokio::fs::read(install_path.join(&source)).await?;
+        *bytes_read += data.len() as u64;
+        steam_workshop_sync_state
+            .lock()
+            .add_bytes(data.len() as u64);
+        tokio::fs::write(&path, &data).await?;
+        output.assets.push(SyncManifestAsset {
+            published_file_id: item.published_file_id,
+            source,
+            path,
+            checksum: Some(get_checksum(&data)),
+        });
+    }
+
+    Ok(output)
+}
+
+/// The result of checking the sync dir against the sync manifest.
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyReport {
+    /// The number of files that match the manifest.
+    pub ok: usize,
+
+    /// Files in the manifest that are not in the sync dir.
+    pub missing: Vec<PathBuf>,
+
+    /// Files that do not match their checksum in the manifest.
+    pub modified: Vec<PathBuf>,
+
+    /// Files in the sync dir that are not in the manifest. Imported packs are not included.
+    pub extra: Vec<PathBuf>,
+}
+
+impl VerifyReport {
+    /// Returns true if there are missing or modified files that can be copied again.
+    pub fn needs_repair(&self) -> bool {
+        !self.missing.is_empty() || !self.modified.is_empty()
+    }
+
+    fn is_broken(&self, path: &Path) -> bool {
+        self.missing
+            .iter()
+            .chain(self.modified.iter())
+            .any(|p| p == path)
+    }
+}
+
+/// Re-hashes the files in the manifest and looks for files in the sync dir that are not in it.
+/// Files synced before checksums were stored are only checked for existence.
+pub async fn verify_sync_dir(
+    sync_dir: &Path,
+    manifest: &SyncManifest,
+) -> Result<VerifyReport, std::io::Error> {
+    let mut report = VerifyReport::default();
+
+    let files = manifest
+        .items
+        .iter()
+        .map(|item| (&item.path, &item.checksum))
+        .chain(
+            manifest
+                .assets
+                .iter()
+                .map(|asset| (&asset.path, &asset.checksum)),
+        );
+    for (path, checksum) in files {
+        let data = match tokio::fs::read(path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                report.missing.push(path.clone());
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        match checksum {
+            Some(checksum) if *checksum != get_checksum(&data) => {
+                report.modified.push(path.clone())
+            }
+            _ => report.ok += 1,
+        }
+    }
+
+    if sync_dir.is_dir() {
+        let imported_dir = get_imported_dir(sync_dir);
+        let mut extra: Vec<PathBuf> = list_files(sync_dir)
+            .await?
+            .into_iter()
+            .filter(|path| !path.starts_with(&imported_dir))
+            .filter(|path| {
+                !manifest.items.iter().any(|item| item.path == *path)
+                    && !manifest.assets.iter().any(|asset| asset.path == *path)
+            })
+            .collect();
+        extra.sort();
+        report.extra = extra;
+    }
+
+    Ok(report)
+}
+
+/// What repair_sync_dir did to the broken files in a verify report.
+#[derive(Debug, Default, Serialize)]
+pub struct RepairReport {
+    /// Files that were copied again.
+    pub repaired: Vec<PathBuf>,
+
+    /// Levels that were removed from the sync dir because their installed version is not a valid level.
+    pub quarantined: Vec<QuarantinedItem>,
+}
+
+/// Copies the missing and modified files in the report again from the install folders of their items,
+/// and updates their checksums and versions in the manifest.
+/// Levels that are no longer valid are quarantined and removed from the sync dir and the manifest instead.
+/// Files of items that are no longer installed are skipped.
+pub async fn repair_sync_dir(
+    steam_client: &steamworks::Client,
+    sync_dir: &Path,
+    data_dir: &Path,
+    manifest: &mut SyncManifest,
+    report: &VerifyReport,
+) -> Result<RepairReport, std::io::Error> {
+    let ugc = steam_client.ugc();
+    let get_install_info = |published_file_id: u64| {
+        ugc.item_install_info(steamworks::PublishedFileId(published_file_id))
+            .map(|install_info| (PathBuf::from(install_info.folder), install_info.timestamp))
+    };
+
+    repair_sync_dir_with(get_install_info, sync_dir, data_dir, manifest, report).await
+}
+
+/// Repairs the sync dir like repair_sync_dir.
+/// get_install_info gets the install folder and timestamp of an item, or None if it is not installed.
+async fn repair_sync_dir_with<F>(
+    get_install_info: F,
+    sync_dir: &Path,
+    data_dir: &Path,
+    manifest: &mut SyncManifest,
+    report: &VerifyReport,
+) -> Result<RepairReport, std::io::Error>
+where
+    F: Fn(u64) -> Option<(PathBuf, u32)>,
+{
+    let get_install_info = |published_file_id: u64| {
+        let install_info = get_install_info(published_file_id);
+        if install_info.is_none() {
+            warn!(
+                "{} is not installed, so it can't be repaired",
+                published_file_id
+            );
+        }
+        install_info
+    };
+
+    // The manifest may have been edited, so files outside the sync dir are never written or removed.
+    let is_repairable = |path: &Path| {
+        let is_repairable = is_in_dir(path, sync_dir);
+        if !is_repairable {
+            warn!(
+                "'{}' is not in the sync dir, so it can't be repaired",
+                path.display()
+            );
+        }
+        is_repairable
+    };
+
+    let mut repair_report = RepairReport::default();
+    for item in manifest
+        .items
+        .iter_mut()
+        .filter(|item| report.is_broken(&item.path) && is_repairable(&item.path))
+    {
+        let (install_path, timestamp) = match get_install_info(item.published_file_id) {
+            Some(install_info) => install_info,
+            None => continue,
+        };
+        let source_path = match item.source.as_ref() {
+            Some(source) => install_path.join(source),
+            None => install_path,
+        };
+
+        let data = tokio::fs::read(&source_path).await?;
+        if let Err(reason) = check_level(&data) {
+            warn!(
+                "Quarantining '{}' ({}) from '{}': {}",
+                item.title,
+                item.published_file_id,
+                source_path.display(),
+                reason
+            );
+
+            let quarantine_name = match item.source.as_ref().and_then(|source| source.file_stem()) {
+                Some(file_stem) => {
+                    format!("{}_{}", item.published_file_id, file_stem.to_string_lossy())
+                }
+                None => item.published_file_id.to_string(),
+            };
+            let quarantine_path =
+                quarantine_level(data_dir, &quarantine_name, &data, &reason).await?;
+            match tokio::fs::remove_file(&item.path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+
+            repair_report.quarantined.push(QuarantinedItem {
+                published_file_id: item.published_file_id,
+                title: item.title.clone(),
+                path: quarantine_path,
+                reason,
+                source: item.source.clone(),
+            });
+            continue;
+        }
+
+        item.checksum = Some(repair_file(&item.path, &data).await?);
+
+        // The installed version may be newer than the one that was synced.
+        item.time_updated = timestamp;
+        repair_report.repaired.push(item.path.clone());
+    }
+    for asset in manifest
+        .assets
+        .iter_mut()
+        .filter(|asset| report.is_broken(&asset.path) && is_repairable(&asset.path))
+    {
+        let install_path = match get_install_info(asset.published_file_id) {
+            Some((install_path, _)) => install_path,
+            None => continue,
+        };
+
+        let data = tokio::fs::read(install_path.join(&asset.source)).await?;
+        asset.checksum = Some(repair_file(&asset.path, &data).await?);
+        repair_report.repaired.push(asset.path.clone());
+    }
+
+    // Quarantined levels replace any older quarantined copies of the same levels.
+    for quarantined in repair_report.quarantined.iter() {
+        manifest.items.retain(|item| {
+            item.published_file_id != quarantined.published_file_id
+                || item.source != quarantined.source
+        });
+        manifest.quarantined.retain(|item| {
+            item.published_file_id != quarantined.published_file_id
+                || item.source != quarantined.source
+        });
+        manifest.quarantined.push(quarantined.clone());
+    }
+
+    Ok(repair_report)
+}
+
+/// Writes the data of a file copied again from an install folder to the sync dir, returning its checksum.
+async fn repair_file(path: &Path, data: &[u8]) -> Result<String, std::io::Error> {
+    debug!("Repairing '{}'", path.display());
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, data).await?;
+
+    Ok(get_checksum(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        level::tests::make_level,
+        util::get_test_dir,
+    };
+
+    /// Makes items installed as single level files in the dir, to be synced to a list dir in it.
+    fn make_item_copies(dir: &Path, len: usize) -> Vec<ItemCopy> {
+        let install_dir = dir.join("install");
+        std::fs::create_dir_all(&install_dir).expect("failed to create install dir");
+        let data = make_level(&[(1, 16, "P0"), (30, 16, "E0"), (5, 17, "B0")]);
+
+        (0..len)
+            .map(|i| {
+                let install_path = install_dir.join(format!("{}.txt", i));
+                std::fs::write(&install_path, &data).expect("failed to write level");
+                ItemCopy {
+                    published_file_id: i as u64,
+                    title: format!("Level {}", i),
+                    name: format!("Level {}", i),
+                    time_updated: 0,
+                    install_path,
+                    size: data.len() as u64,
+                    list_dir: dir.join("sync").join("subscribed"),
+                }
+            })
+            .collect()
+    }
+
+    /// Copies the items with the default retry policy.
+    fn run_copy_items(
+        tokio_rt: &mut TokioRuntime,
+        item_copies: Vec<ItemCopy>,
+        data_dir: &Path,
+        concurrency: usize,
+    ) -> Result<Vec<(u64, ItemCopyOutput)>, SteamWorkshopSyncError> {
+        let steam_workshop_sync_state = Arc::new(Mutex::new(SteamWorkshopSyncState::Starting));
+        let total_bytes = item_copies.iter().map(|item_copy| item_copy.size).sum();
+        steam_workshop_sync_state
+            .lock()
+            .begin_sync(item_copies.len(), total_bytes);
+
+        tokio_rt.block_on(copy_items(
+            item_copies,
+            data_dir,
+            concurrency,
+            &RetryPolicy::default(),
+            &steam_workshop_sync_state,
+        ))
+    }
+
+    #[test]
+    fn copy_items_keeps_order() {
+        let dir = get_test_dir("copy-items-order");
+        let item_copies = make_item_copies(&dir, 20);
+        let mut tokio_rt = TokioRuntime::new().expect("failed to start tokio");
+
+        let item_outputs =
+            run_copy_items(&mut tokio_rt, item_copies, &dir, 4).expect("failed to copy items");
+        let published_file_ids: Vec<u64> = item_outputs
+            .iter()
+            .map(|(published_file_id, _)| *published_file_id)
+            .collect();
+        assert_eq!(published_file_ids, (0..20).collect::<Vec<u64>>());
+        for (published_file_id, output) in item_outputs.iter() {
+            assert_eq!(output.items.len(), 1);
+            assert!(output.quarantined.is_empty());
+            assert_eq!(
+                output.items[0].path,
+                dir.join("sync")
+                    .join("subscribed")
+                    .join(format!("Level {}.txt", published_file_id))
+            );
+            assert!(output.items[0].path.exists());
+        }
+    }
+
+    #[test]
+    fn copy_items_fails_on_first_error() {
+        let dir = get_test_dir("copy-items-error");
+        let mut item_copies = make_item_copies(&dir, 20);
+        item_copies[0].install_path = dir.join("missing.txt");
+        let mut tokio_rt = TokioRuntime::new().expect("failed to start tokio");
+
+        // Only one copy runs at a time, so most copies after the failed one are skipped.
+        let result = run_copy_items(&mut tokio_rt, item_copies, &dir, 1);
+        assert!(matches!(result, Err(SteamWorkshopSyncError::Io(_))));
+
+        let copied = std::fs::read_dir(dir.join("sync").join("subscribed"))
+            .expect("failed to read list dir")
+            .count();
+        assert!(copied < 19);
+    }
+
+    #[test]
+    fn run_item_copies_waits_for_running_copies() {
+        let dir = get_test_dir("run-item-copies");
+        let item_copies = make_item_copies(&dir, 20);
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        let (failed_tx, failed_rx) = tokio::sync::oneshot::channel();
+        let failed_tx = Arc::new(Mutex::new(Some(failed_tx)));
+        let failed_rx = Arc::new(Mutex::new(Some(failed_rx)));
+        let is_finished = Arc::new(AtomicBool::new(false));
+
+        // The first copy fails once the second one has started, and the second one finishes after that.
+        let copy = {
+            let is_finished = is_finished.clone();
+            move |item_copy: ItemCopy| {
+                let barrier = barrier.clone();
+                let failed_tx = failed_tx.clone();
+                let failed_rx = failed_rx.clone();
+                let is_finished = is_finished.clone();
+                async move {
+                    match item_copy.published_file_id {
+                        0 => {
+                            barrier.wait().await;
+                            if let Some(failed_tx) = failed_tx.lock().take() {
+                                let _ = failed_tx.send(());
+                            }
+                            Err(SteamWorkshopSyncError::Io(std::io::ErrorKind::Other.into()))
+                        }
+                        1 => {
+                            barrier.wait().await;
+                            let failed_rx = failed_rx.lock().take();
+                            if let Some(failed_rx) = failed_rx {
+                                let _ = failed_rx.await;
+                            }
+                            is_finished.store(true, Ordering::SeqCst);
+                            Ok(())
+                        }
+                        _ => Ok(()),
+                    }
+                }
+            }
+        };
+
+        let mut tokio_rt = TokioRuntime::new().expect("failed to start tokio");
+        let result = tokio_rt.block_on(run_item_copies(item_copies, 2, copy));
+        assert!(matches!(result, Err(SteamWorkshopSyncError::Io(_))));
+        assert!(is_finished.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn merge_list_results_dedupes_items() {
+        let list_results = vec![
+            (SyncList::Subscribed, vec![(1, "a"), (2, "b")]),
+            (SyncList::Published, vec![(3, "c"), (1, "a again")]),
+            (SyncList::Favorited, vec![(2, "b again"), (1, "a again")]),
+        ];
+        let sync_entries =
+            merge_list_results(list_results, |(published_file_id, _)| *published_file_id);
+
+        let merged: Vec<(u64, &str, Vec<SyncList>)> = sync_entries
+            .into_iter()
+            .map(|sync_entry| (sync_entry.entry.0, sync_entry.entry.1, sync_entry.lists))
+            .collect();
+        assert_eq!(
+            merged,
+            vec![
+                (
+                    1,
+                    "a",
+                    vec![
+                        SyncList::Subscribed,
+                        SyncList::Published,
+                        SyncList::Favorited
+                    ]
+                ),
+                (2, "b", vec![SyncList::Subscribed, SyncList::Favorited]),
+                (3, "c", vec![SyncList::Published]),
+            ]
+        );
+    }
+
+    /// Detects the layout of an install path, returning the level file names and assets it would be synced as.
+    fn detect_level_file_names(install_path: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let mut tokio_rt = TokioRuntime::new().expect("failed to start tokio");
+        match tokio_rt
+            .block_on(ItemLayout::detect(install_path))
+            .expect("failed to detect layout")
+        {
+            ItemLayout::File => (vec![get_level_file_name("Level", None, false)], Vec::new()),
+            ItemLayout::Dir { levels, assets } => {
+                let is_pack = levels.len() > 1;
+                let level_file_names = levels
+                    .iter()
+                    .map(|source| get_level_file_name("Level", Some(source), is_pack))
+                    .collect();
+                (level_file_names, assets)
+            }
+        }
+    }
+
+    #[test]
+    fn detect_single_file_item() {
+        let dir = get_test_dir("layout-file");
+        let install_path = dir.join("level.txt");
+        std::fs::write(&install_path, "level").expect("failed to write level");
+
+        assert_eq!(
+            detect_level_file_names(&install_path),
+            (vec![PathBuf::from("Level.txt")], Vec::new())
+        );
+    }
+
+    #[test]
+    fn detect_folder_item() {
+        let dir = get_test_dir("layout-folder");
+        std::fs::write(dir.join("level"), "level").expect("failed to write level");
+
+        assert_eq!(
+            detect_level_file_names(&dir),
+            (vec![PathBuf::from("Level.txt")], Vec::new())
+        );
+
+        std::fs::write(dir.join("level"), "asset").expect("failed to write asset");
+        std::fs::write(dir.join("level.txt"), "level").expect("failed to write level");
+        assert_eq!(
+            detect_level_file_names(&dir),
+            (
+                vec![PathBuf::from("Level.txt")],
+                vec![PathBuf::from("level")]
+            )
+        );
+    }
+
+    #[test]
+    fn detect_nested_pack_item() {
+        let dir = get_test_dir("layout-pack");
+        std::fs::create_dir_all(dir.join("world 1").join("art")).expect("failed to create dirs");
+        std::fs::write(dir.join("b.txt"), "level").expect("failed to write level");
+        std::fs::write(dir.join("world 1").join("a.txt"), "level").expect("failed to write level");
+        std::fs::write(dir.join("world 1").join("art").join("bg.png"), "asset")
+            .expect("failed to write asset");
+
+        assert_eq!(
+            detect_level_file_names(&dir),
+            (
+                vec![
+                    PathBuf::from("Level - b.txt"),
+                    PathBuf::from("Level - world 1 - a.txt")
+                ],
+                vec![Path::new("world 1").join("art").join("bg.png")]
+            )
+        );
+    }
+
+    #[test]
+    fn get_item_name_adds_id_on_collision() {
+        let dir = Path::new("sync");
+        let mut item_copies = make_item_copies(&get_test_dir("item-name"), 1);
+        item_copies[0].list_dir = dir.join("subscribed");
+
+        let subscribed_dir = dir.join("subscribed");
+        assert_eq!(
+            get_item_name(&item_copies, &subscribed_dir, "../Level 0", 7),
+            ".._Level 0"
+        );
+        assert_eq!(
+            get_item_name(&item_copies, &subscribed_dir, "level 0", 7),
+            "level 0 (7)"
+        );
+        assert_eq!(
+            get_item_name(&item_copies, &dir.join("published"), "Level 0", 7),
+            "Level 0"
+        );
+    }
+
+    #[test]
+    fn is_in_dir_rejects_parent_dirs() {
+        let dir = Path::new("sync");
+        assert!(is_in_dir(&dir.join("subscribed").join("a.txt"), dir));
+        assert!(!is_in_dir(dir, dir));
+        assert!(!is_in_dir(Path::new("other/a.txt"), dir));
+        assert!(!is_in_dir(&dir.join("..").join("a.txt"), dir));
+        assert!(!is_in_dir(&dir.join("subscribed/../../a.txt"), dir));
+    }
+
+    /// Makes a manifest item synced from a single file, with the checksum of the data.
+    fn make_manifest_item(published_file_id: u64, path: PathBuf, data: &[u8]) -> SyncManifestItem {
+        SyncManifestItem {
+            published_file_id,
+            title: format!("Level {}", published_file_id),
+            time_updated: 0,
+            path,
+            source: None,
+            checksum: Some(get_checksum(data)),
+        }
+    }
+
+    #[test]
+    fn verify_sync_dir_finds_broken_files() {
+        let dir = get_test_dir("verify");
+        let sync_dir = dir.join("sync");
+        let list_dir = sync_dir.join("subscribed");
+        std::fs::create_dir_all(&list_dir).expect("failed to create list dir");
+        let data = make_level(&[(1, 16, "P0"), (30, 16, "E0"), (5, 17, "B0")]);
+
+        std::fs::write(list_dir.join("ok.txt"), &data).expect("failed to write level");
+        std::fs::write(list_dir.join("modified.txt"), "modified").expect("failed to write level");
+        std::fs::write(list_dir.join("extra.txt"), &data).expect("failed to write level");
+        let imported_dir = get_imported_dir(&sync_dir).join("pack");
+        std::fs::create_dir_all(&imported_dir).expect("failed to create imported dir");
+        std::fs::write(imported_dir.join("imported.txt"), &data).expect("failed to write level");
+
+        let manifest = SyncManifest {
+            items: vec![
+                make_manifest_item(1, list_dir.join("ok.txt"), data.as_bytes()),
+                make_manifest_item(2, list_dir.join("missing.txt"), data.as_bytes()),
+                make_manifest_item(3, list_dir.join("modified.txt"), data.as_bytes()),
+            ],
+            quarantined: Vec::new(),
+            assets: vec![SyncManifestAsset {
+                published_file_id: 1,
+                source: PathBuf::from("bg.png"),
+                path: list_dir.join("bg.png"),
+                checksum: None,
+            }],
+        };
+
+        let mut tokio_rt = TokioRuntime::new().expect("failed to start tokio");
+        let report = tokio_rt
+            .block_on(verify_sync_dir(&sync_dir, &manifest))
+            .expect("failed to verify sync dir");
+
+        assert_eq!(report.ok, 1);
+        assert_eq!(
+            report.missing,
+            vec![list_dir.join("missing.txt"), list_dir.join("bg.png")]
+        );
+        assert_eq!(report.modified, vec![list_dir.join("modified.txt")]);
+        assert_eq!(report.extra, vec![list_dir.join("extra.txt")]);
+        assert!(report.needs_repair());
+    }
+
+    #[test]
+    fn repair_sync_dir_copies_and_quarantines() {
+        let dir = get_test_dir("repair");
+        let sync_dir = dir.join("sync");
+        let data_dir = dir.join("data");
+        let install_dir = dir.join("install");
+        let list_dir = sync_dir.join("subscribed");
+        std::fs::create_dir_all(&list_dir).expect("failed to create list dir");
+        std::fs::create_dir_all(&install_dir).expect("failed to create install dir");
+        let data = make_level(&[(1, 16, "P0"), (30, 16, "E0"), (5, 17, "B0")]);
+
+        // 1 is installed and valid, 2 was updated to an invalid level, and 3 is no longer installed.
+        std::fs::write(install_dir.join("1.txt"), &data).expect("failed to write level");
+        std::fs::write(install_dir.join("2.txt"), "invalid").expect("failed to write level");
+        std::fs::write(list_dir.join("2.txt"), "modified").expect("failed to write level");
+        let outside_path = sync_dir.join("..").join("outside.txt");
+        let mut manifest = SyncManifest {
+            items: vec![
+                make_manifest_item(1, list_dir.join("1.txt"), data.as_bytes()),
+                make_manifest_item(2, list_dir.join("2.txt"), data.as_bytes()),
+                make_manifest_item(3, list_dir.join("3.txt"), data.as_bytes()),
+                make_manifest_item(1, outside_path.clone(), data.as_bytes()),
+            ],
+            quarantined: Vec::new(),
+            assets: Vec::new(),
+        };
+        let report = VerifyReport {
+            ok: 0,
+            missing: vec![
+                list_dir.join("1.txt"),
+                list_dir.join("3.txt"),
+                outside_path.clone(),
+            ],
+            modified: vec![list_dir.join("2.txt")],
+            extra: Vec::new(),
+        };
+        let get_install_info = |published_file_id: u64| match published_file_id {
+            1 | 2 => Some((install_dir.join(format!("{}.txt", published_file_id)), 10)),
+            _ => None,
+        };
+
+        let mut tokio_rt = TokioRuntime::new().expect("failed to start tokio");
+        let repair_report = tokio_rt
+            .block_on(repair_sync_dir_with(
+                get_install_info,
+                &sync_dir,
+                &data_dir,
+                &mut manifest,
+                &report,
+            ))
+            .expect("failed to repair sync dir");
+
+        assert_eq!(repair_report.repaired, vec![list_dir.join("1.txt")]);
+        assert_eq!(
+            std::fs::read_to_string(list_dir.join("1.txt")).expect("failed to read level"),
+            data
+        );
+        assert_eq!(manifest.items[0].time_updated, 10);
+        assert!(!outside_path.exists());
+
+        assert_eq!(repair_report.quarantined.len(), 1);
+        let quarantined = &repair_report.quarantined[0];
+        assert_eq!(quarantined.published_file_id, 2);
+        assert_eq!(
+            quarantined.path,
+            get_quarantine_dir(&data_dir).join("2.txt")
+        );
+        assert_eq!(
+            std::fs::read_to_string(&quarantined.path).expect("failed to read quarantined level"),
+            "invalid"
+        );
+        assert!(!list_dir.join("2.txt").exists());
+
+        let published_file_ids: Vec<u64> = manifest
+            .items
+            .iter()
+            .map(|item| item.published_file_id)
+            .collect();
+        assert_eq!(published_file_ids, vec![1, 3, 1]);
+        assert_eq!(manifest.quarantined.len(), 1);
+    }
+
+    /// Retries a scripted copy that reads the given bytes and returns the given result on each attempt.
+    /// Returns the result and the number of attempts that were made.
+    fn run_scripted_copy(
+        steam_workshop_sync_state: &Mutex<SteamWorkshopSyncState>,
+        attempts: Vec<(Result<(), std::io::ErrorKind>, u64)>,
+    ) -> (Result<(), SteamWorkshopSyncError>, usize) {
+        let retry_policy = RetryPolicy {
+            initial_delay_ms: 1,
+            ..RetryPolicy::default()
+        };
+        let item_copy = ItemCopy {
+            published_file_id: 1,
+            title: String::from("Level"),
+            name: String::from("Level"),
+            time_updated: 0,
+            install_path: PathBuf::from("install"),
+            size: 0,
+            list_dir: PathBuf::from("sync"),
+        };
+
+        let len = attempts.len();
+        let mut attempts = attempts.into_iter();
+        let mut tokio_rt = TokioRuntime::new().expect("failed to start tokio");
+        let result = tokio_rt.block_on(copy_with_retry(
+            &retry_policy,
+            steam_workshop_sync_state,
+            &item_copy,
+            || {
+                let (result, bytes_read) = attempts.next().expect("too many attempts");
+                steam_workshop_sync_state.lock().add_bytes(bytes_read);
+                let result =
+                    result.map_err(|kind| SteamWorkshopSyncError::from(std::io::Error::from(kind)));
+                async move { (result, bytes_read) }
+            },
+        ));
+
+        (result, len - attempts.len())
+    }
+
+    fn get_bytes_synced(steam_workshop_sync_state: &Mutex<SteamWorkshopSyncState>) -> u64 {
+        match &*steam_workshop_sync_state.lock() {
+            SteamWorkshopSyncState::InProgress { bytes_synced, .. } => *bytes_synced,
+            state => panic!("sync is not in progress: {:?}", state),
+        }
+    }
+
+    #[test]
+    fn copy_with_retry_takes_back_bytes() {
+        let steam_workshop_sync_state = Mutex::new(SteamWorkshopSyncState::Starting);
+        steam_workshop_sync_state.lock().begin_sync(1, 100);
+
+        let (result, attempts) = run_scripted_copy(
+            &steam_workshop_sync_state,
+            vec![
+                (Err(std::io::ErrorKind::Interrupted), 30),
+                (Err(std::io::ErrorKind::TimedOut), 40),
+                (Ok(()), 100),
+            ],
+        );
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+        assert_eq!(get_bytes_synced(&steam_workshop_sync_state), 100);
+    }
+
+    #[test]
+    fn copy_with_retry_stops_on_non_retryable() {
+        let steam_workshop_sync_state = Mutex::new(SteamWorkshopSyncState::Starting);
+        steam_workshop_sync_state.lock().begin_sync(1, 100);
+
+        let (result, attempts) = run_scripted_copy(
+            &steam_workshop_sync_state,
+            vec![(Err(std::io::ErrorKind::NotFound), 30), (Ok(()), 100)],
+        );
+        assert!(matches!(result, Err(SteamWorkshopSyncError::Io(_))));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn copy_with_retry_stops_at_max_attempts() {
+        let steam_workshop_sync_state = Mutex::new(SteamWorkshopSyncState::Starting);
+        steam_workshop_sync_state.lock().begin_sync(1, 100);
+
+        let (result, attempts) = run_scripted_copy(
+            &steam_workshop_sync_state,
+            vec![
+                (Err(std::io::ErrorKind::Interrupted), 30),
+                (Err(std::io::ErrorKind::Interrupted), 30),
+                (Err(std::io::ErrorKind::Interrupted), 30),
+                (Ok(()), 100),
+            ],
+        );
+        assert!(matches!(result, Err(SteamWorkshopSyncError::Io(_))));
+        assert_eq!(attempts, RetryPolicy::default().max_attempts as usize);
+
+        // Only the bytes of the last attempt are still counted.
+        assert_eq!(get_bytes_synced(&steam_workshop_sync_state), 30);
+    }
+}
@@ -1,26 +1,107 @@
 use crate::{
-    config::Config,
+    config::{
+        Config,
+        ConfigWatcher,
+    },
+    diagnostics::GpuInfo,
+    level::{
+        Level,
+        LevelError,
+        LEVEL_HEIGHT,
+        LEVEL_WIDTH,
+        PREVIEW_TILE_SIZE,
+    },
+    library::{
+        get_level_name,
+        scan_levels,
+        Library,
+        LibraryError,
+    },
+    pack::{
+        export_pack,
+        get_default_pack_path,
+        get_pack_sources,
+        import_pack,
+        PackError,
+        PackManifest,
+    },
+    preview::{
+        load_thumbnail,
+        Fetcher,
+        FileFetcher,
+        HttpFetcher,
+        PreviewCache,
+        THUMBNAIL_SIZE,
+    },
+    publish::{
+        spawn_publish,
+        spawn_published_items_query,
+        spawn_update,
+        PublishRequest,
+        PublishState,
+        PublishedItem,
+        UpdateRequest,
+    },
     steamworks_util::{
+        subscribe_item,
+        unsubscribe_item,
+        CallResultPoller,
+        DownloadItemResult,
+        ItemInstalled,
+        ItemVisibility,
         OneShotRecvError,
-        UgcQueryBuilder,
+    },
+    sync::{
+        ensure_sync_dir,
+        get_sync_manifest_path,
+        spawn_steam_workshop_sync,
+        SteamWorkshopSyncError,
+        SteamWorkshopSyncState,
+        SyncManifest,
+    },
+    workshop::{
+        filter_and_sort,
+        ItemAction,
+        WorkshopCache,
+        WorkshopItem,
+        WorkshopItemSort,
+        WorkshopItemUserState,
     },
 };
 use conrod_core::{
     widget,
     widget_ids,
-    Borderable,
     Colorable,
     Labelable,
     Positionable,
     Sizeable,
     Widget,
 };
+use image::RgbaImage;
+use log::{
+    debug,
+    error,
+    info,
+    warn,
+};
 use parking_lot::Mutex;
 use std::{
     borrow::Cow,
-    error::Error as StdError,
-    path::PathBuf,
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    future::Future,
+    path::{
+        Path,
+        PathBuf,
+    },
+    pin::Pin,
     sync::Arc,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 use tokio::runtime::Runtime as TokioRuntime;
 
@@ -30,24 +111,203 @@ widget_ids! {
 
         game_button,
         levelbuilder_button,
+        browse_levels_button,
+        publish_button,
+        my_levels_button,
+        library_button,
 
         cover_image,
 
+        syncing_background,
+        syncing_progress,
         syncing_label,
+
+        toast,
+
+        diagnostics_button,
+        redact_toggle,
+
+        crash_report_label,
+        crash_report_open_button,
+        crash_report_zip_button,
+        crash_report_dismiss_button,
+
+        back_button,
+        level_filter,
+        level_sort,
+        level_list,
+        level_thumbnails[],
+        level_texts[],
+        level_preview,
+
+        level_subscribe_button,
+        level_vote_up_button,
+        level_vote_down_button,
+        level_favorite_button,
+        level_pack_button,
+        level_export_pack_button,
+
+        publish_level_path_label,
+        publish_level_path,
+        publish_title_label,
+        publish_title,
+        publish_description_label,
+        publish_description,
+        publish_tags_label,
+        publish_tags,
+        publish_preview_path_label,
+        publish_preview_path,
+        publish_visibility,
+        publish_submit_button,
+        publish_status,
+        publish_progress_background,
+        publish_progress,
+
+        my_levels_list,
+        my_level_texts[],
+        update_level_path_label,
+        update_level_path,
+        update_change_note_label,
+        update_change_note,
+        update_submit_button,
+
+        library_favorites_only_toggle,
+        library_refresh_button,
+        library_list,
+        library_level_texts[],
+        library_folder,
+        library_add_folder_button,
+        library_details,
+        library_level_name,
+        library_favorite_toggle,
+        library_add_to_playlist_button,
+        library_tags_label,
+        library_tags,
+        library_notes_label,
+        library_notes,
+        library_playlist,
+        library_delete_playlist_button,
+        library_playlist_name,
+        library_new_playlist_button,
+        library_playlist_levels,
+        library_playlist_level_texts[],
+        library_move_up_button,
+        library_move_down_button,
+        library_remove_button,
+        library_export_dir,
+        library_export_button,
     }
 }
 
-pub fn gui(ui: &mut conrod_core::UiCell, ids: &Ids, app: &mut App) {
-    let button_width = 200.0;
-    let button_height = 50.0;
-    let cover_image_side = 200.0;
+/// How often to try to connect to steam while offline.
+const STEAM_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a toast stays on screen.
+const TOAST_DURATION: Duration = Duration::from_secs(5);
 
+/// How long the library must go unchanged before it is saved, so typing in a field does not save on every key.
+const LIBRARY_SAVE_DELAY: Duration = Duration::from_secs(1);
+
+pub fn gui(ui: &mut conrod_core::UiCell, ids: &mut Ids, app: &mut App) {
     widget::Text::new("Skeleton Sprint Launcher")
         .color(conrod_core::color::WHITE)
         .font_size(42)
         .mid_top_of(ui.window)
         .set(ids.title, ui);
 
+    match app.view {
+        View::Home => home_gui(ui, ids, app),
+        View::Levels => levels_gui(ui, ids, app),
+        View::Publish => publish_gui(ui, ids, app),
+        View::MyLevels => my_levels_gui(ui, ids, app),
+        View::Library => library_gui(ui, ids, app),
+    }
+
+    {
+        let steam_workshop_sync_state = app.steam_workshop_sync_state.lock();
+        let (sync_label, progress): (Cow<'_, str>, f64) = match &*steam_workshop_sync_state {
+            SteamWorkshopSyncState::Offline => {
+                ("Steam unavailable – using cached levels".into(), 0.0)
+            }
+            SteamWorkshopSyncState::Starting => ("Syncing...".into(), 0.0),
+            SteamWorkshopSyncState::InProgress {
+                synced,
+                total,
+                bytes_synced,
+                total_bytes,
+                current_title,
+                ..
+            } => {
+                let mut sync_label = format!(
+                    "Syncing({}/{}) {} / {}",
+                    synced,
+                    total,
+                    format_bytes(*bytes_synced),
+                    format_bytes(*total_bytes)
+                );
+                if let Some(eta) = steam_workshop_sync_state.get_eta() {
+                    sync_label.push_str(&format!(", {}s left", eta.as_secs()));
+                }
+                if let Some(current_title) = current_title {
+                    sync_label.push_str(&format!(" – {}", current_title));
+                }
+
+                let progress = if *total_bytes > 0 {
+                    *bytes_synced as f64 / *total_bytes as f64
+                } else if *total > 0 {
+                    *synced as f64 / *total as f64
+                } else {
+                    0.0
+                };
+                (sync_label.into(), progress)
+            }
+            SteamWorkshopSyncState::Done(_) => ("Sync Complete".into(), 1.0),
+            SteamWorkshopSyncState::Failed(_) => ("Sync Failed!".into(), 0.0),
+        };
+        drop(steam_workshop_sync_state);
+
+        let sync_bar_height = 30.0;
+        widget::Rectangle::fill_with([ui.win_w, sync_bar_height], conrod_core::color::WHITE)
+            .bottom_left_of(ui.window)
+            .set(ids.syncing_background, ui);
+
+        widget::Rectangle::fill_with(
+            [ui.win_w * progress.min(1.0), sync_bar_height],
+            conrod_core::color::LIGHT_BLUE,
+        )
+        .top_left_of(ids.syncing_background)
+        .set(ids.syncing_progress, ui);
+
+        widget::Text::new(&sync_label)
+            .color(conrod_core::color::BLACK)
+            .font_size(14)
+            .mid_left_with_margin_on(ids.syncing_background, 10.0)
+            .set(ids.syncing_label, ui);
+    }
+
+    if let Some(toast) = app.toast.as_ref() {
+        let color = if toast.is_error {
+            conrod_core::color::LIGHT_RED
+        } else {
+            conrod_core::color::LIGHT_GREEN
+        };
+
+        widget::Text::new(&toast.message)
+            .color(color)
+            .font_size(14)
+            .w(ui.win_w - 20.0)
+            .center_justify()
+            .mid_bottom_with_margin_on(ui.window, 40.0)
+            .set(ids.toast, ui);
+    }
+}
+
+/// The main view, with the launch buttons.
+fn home_gui(ui: &mut conrod_core::UiCell, ids: &Ids, app: &mut App) {
+    let button_width = 200.0;
+    let button_height = 50.0;
+    let cover_image_side = 200.0;
+
     widget::Image::new(app.cover_image)
         .w_h(cover_image_side, cover_image_side)
         .down_from(ids.title, 10.0)
@@ -77,233 +337,2475 @@ pub fn gui(ui: &mut conrod_core::UiCell, ids: &Ids, app: &mut App) {
         crate::util::open_program(&*levelbuilder_path.to_string_lossy());
     }
 
+    for () in widget::Button::new()
+        .label("Browse Levels")
+        .down_from(ids.levelbuilder_button, 10.0)
+        .w_h(button_width, button_height)
+        .set(ids.browse_levels_button, ui)
     {
-        let steam_workshop_sync_state = app.steam_workshop_sync_state.lock();
-        let sync_label: Cow<'_, str> = match &*steam_workshop_sync_state {
-            SteamWorkshopSyncState::Starting => "Syncing...".into(),
-            SteamWorkshopSyncState::InProgress(current, total) => {
-                format!("Syncing({}/{})...", current, total).into()
-            }
-            SteamWorkshopSyncState::Done => "Sync Complete".into(),
-            SteamWorkshopSyncState::Failed(_) => "Sync Failed!".into(),
-        };
+        app.view = View::Levels;
+    }
 
-        widget::TitleBar::new(&sync_label, ui.window)
-            .color(conrod_core::Color::Rgba(1.0, 1.0, 1.0, 1.0))
-            .bottom_left_with_margin_on(ui.window, 0.0)
-            .w_h(200.0, 30.0)
-            .border(0.0)
-            .set(ids.syncing_label, ui);
+    for () in widget::Button::new()
+        .label("Library")
+        .right_from(ids.browse_levels_button, 10.0)
+        .w_h(button_width, button_height)
+        .set(ids.library_button, ui)
+    {
+        app.view = View::Library;
+        app.refresh_library();
     }
-}
 
-#[derive(Debug)]
-pub enum AppError {
-    Io(std::io::Error),
-    Steam(steamworks::SteamError),
-    SteamWorkshopQuery(crate::steamworks_util::WorkshopQueryError),
+    for () in widget::Button::new()
+        .label("Publish Level")
+        .down_from(ids.browse_levels_button, 10.0)
+        .w_h(button_width, button_height)
+        .set(ids.publish_button, ui)
+    {
+        app.view = View::Publish;
+    }
 
-    InvalidSyncDir,
-}
+    for () in widget::Button::new()
+        .label("My Levels")
+        .down_from(ids.publish_button, 10.0)
+        .w_h(button_width, button_height)
+        .set(ids.my_levels_button, ui)
+    {
+        app.view = View::MyLevels;
+        app.refresh_published_items();
+    }
 
-impl From<std::io::Error> for AppError {
-    fn from(e: std::io::Error) -> AppError {
-        AppError::Io(e)
+    for () in widget::Button::new()
+        .label("Export Diagnostics")
+        .label_font_size(14)
+        .w_h(150.0, 30.0)
+        .top_right_with_margin_on(ui.window, 10.0)
+        .set(ids.diagnostics_button, ui)
+    {
+        app.export_diagnostics();
     }
-}
 
-impl From<steamworks::SteamError> for AppError {
-    fn from(e: steamworks::SteamError) -> AppError {
-        AppError::Steam(e)
+    for redact_paths in widget::Toggle::new(app.redact_diagnostics)
+        .label("Redact Paths")
+        .label_font_size(12)
+        .w_h(150.0, 20.0)
+        .down_from(ids.diagnostics_button, 5.0)
+        .set(ids.redact_toggle, ui)
+    {
+        app.redact_diagnostics = redact_paths;
     }
-}
 
-impl From<crate::steamworks_util::WorkshopQueryError> for AppError {
-    fn from(e: crate::steamworks_util::WorkshopQueryError) -> AppError {
-        AppError::SteamWorkshopQuery(e)
+    if app.pending_crash_report.is_some() {
+        let crash_button_width = 150.0;
+        let crash_button_height = 30.0;
+
+        for () in widget::Button::new()
+            .label("Dismiss")
+            .label_font_size(14)
+            .w_h(crash_button_width, crash_button_height)
+            .bottom_right_with_margin_on(ui.window, 10.0)
+            .set(ids.crash_report_dismiss_button, ui)
+        {
+            app.dismiss_crash_report();
+        }
+
+        for () in widget::Button::new()
+            .label("Zip Report")
+            .label_font_size(14)
+            .w_h(crash_button_width, crash_button_height)
+            .up_from(ids.crash_report_dismiss_button, 5.0)
+            .set(ids.crash_report_zip_button, ui)
+        {
+            app.zip_crash_report();
+        }
+
+        for () in widget::Button::new()
+            .label("Open Report")
+            .label_font_size(14)
+            .w_h(crash_button_width, crash_button_height)
+            .up_from(ids.crash_report_zip_button, 5.0)
+            .set(ids.crash_report_open_button, ui)
+        {
+            app.open_crash_report();
+        }
+
+        widget::Text::new("The launcher crashed last time.")
+            .color(conrod_core::color::WHITE)
+            .font_size(14)
+            .up_from(ids.crash_report_open_button, 5.0)
+            .align_right_of(ids.crash_report_open_button)
+            .set(ids.crash_report_label, ui);
     }
 }
 
-impl std::fmt::Display for AppError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Io(e) => e.fmt(f),
-            Self::Steam(e) => e.fmt(f),
-            Self::SteamWorkshopQuery(e) => e.fmt(f),
-            Self::InvalidSyncDir => write!(f, "The sync dir is invalid"),
+/// The workshop browser view, listing the subscribed items.
+fn levels_gui(ui: &mut conrod_core::UiCell, ids: &mut Ids, app: &mut App) {
+    let margin = 10.0;
+    let control_height = 30.0;
+    let list_item_height = 70.0;
+    let thumbnail_side = f64::from(THUMBNAIL_SIZE);
+    let preview_width = f64::from(LEVEL_WIDTH as u32 * PREVIEW_TILE_SIZE);
+    let preview_height = f64::from(LEVEL_HEIGHT as u32 * PREVIEW_TILE_SIZE);
+    let list_width = ui.win_w - preview_width - (margin * 3.0);
+    let list_height = ui.win_h - 230.0;
+
+    for () in widget::Button::new()
+        .label("Back")
+        .label_font_size(14)
+        .w_h(100.0, control_height)
+        .top_left_with_margin_on(ui.window, margin)
+        .set(ids.back_button, ui)
+    {
+        app.view = View::Home;
+    }
+
+    for event in widget::TextBox::new(&app.level_filter)
+        .font_size(14)
+        .w_h(list_width - 210.0, control_height)
+        .down_from(ids.title, margin)
+        .align_left_of(ids.back_button)
+        .set(ids.level_filter, ui)
+    {
+        if let widget::text_box::Event::Update(filter) = event {
+            app.level_filter = filter;
         }
     }
-}
 
-#[derive(Debug)]
-pub enum SteamWorkshopSyncError {
-    Recieve(OneShotRecvError),
-    Steam(steamworks::SteamError),
-    Io(std::io::Error),
+    let sort_labels: Vec<&str> = WorkshopItemSort::ALL
+        .iter()
+        .map(|sort| sort.as_str())
+        .collect();
+    let selected_sort = WorkshopItemSort::ALL
+        .iter()
+        .position(|sort| *sort == app.level_sort);
+    for selected in widget::DropDownList::new(&sort_labels, selected_sort)
+        .label_font_size(14)
+        .w_h(200.0, control_height)
+        .right_from(ids.level_filter, margin)
+        .set(ids.level_sort, ui)
+    {
+        app.level_sort = WorkshopItemSort::ALL[selected];
+    }
 
-    MissingItemInfo,
-}
+    for () in widget::Button::new()
+        .label(&format!("Export Pack ({})", app.pack_selection.len()))
+        .label_font_size(14)
+        .w_h(preview_width, control_height)
+        .right_from(ids.level_sort, margin)
+        .set(ids.level_export_pack_button, ui)
+    {
+        app.export_pack();
+    }
+
+    let workshop_items = app.workshop_items.lock();
+    let indices = filter_and_sort(&workshop_items, &app.level_filter, app.level_sort);
+
+    ids.level_thumbnails
+        .resize(indices.len(), &mut ui.widget_id_generator());
+    ids.level_texts
+        .resize(indices.len(), &mut ui.widget_id_generator());
+    let selected_index = app.selected_level.and_then(|published_file_id| {
+        indices
+            .iter()
+            .position(|i| workshop_items[*i].published_file_id == published_file_id)
+    });
 
-impl From<OneShotRecvError> for SteamWorkshopSyncError {
-    fn from(e: OneShotRecvError) -> Self {
-        Self::Recieve(e)
+    let (mut events, scrollbar) = widget::ListSelect::single(indices.len())
+        .flow_down()
+        .item_size(list_item_height)
+        .scrollbar_next_to()
+        .w_h(list_width, list_height)
+        .down_from(ids.level_filter, margin)
+        .align_left_of(ids.level_filter)
+        .set(ids.level_list, ui);
+
+    while let Some(event) = events.next(ui, |i| Some(i) == selected_index) {
+        use conrod_core::widget::list_select::Event;
+        match event {
+            Event::Item(list_item) => {
+                let i = list_item.i;
+                let item_id = list_item.widget_id;
+                let workshop_item = &workshop_items[indices[i]];
+                let is_selected = Some(i) == selected_index;
+
+                let background_color = if is_selected {
+                    conrod_core::color::DARK_BLUE
+                } else {
+                    conrod_core::color::TRANSPARENT
+                };
+                list_item.set(
+                    widget::Rectangle::fill_with([list_width, list_item_height], background_color),
+                    ui,
+                );
+
+                let thumbnail_id = ids.level_thumbnails[i];
+                match app.preview_images.get(&workshop_item.published_file_id) {
+                    Some(image_id) => widget::Image::new(*image_id)
+                        .w_h(thumbnail_side, thumbnail_side)
+                        .mid_left_with_margin_on(item_id, margin / 2.0)
+                        .parent(item_id)
+                        .graphics_for(item_id)
+                        .set(thumbnail_id, ui),
+                    None => widget::Rectangle::fill_with(
+                        [thumbnail_side, thumbnail_side],
+                        conrod_core::color::DARK_CHARCOAL,
+                    )
+                    .mid_left_with_margin_on(item_id, margin / 2.0)
+                    .parent(item_id)
+                    .graphics_for(item_id)
+                    .set(thumbnail_id, ui),
+                }
+
+                let text = format_workshop_item(workshop_item);
+                widget::Text::new(&text)
+                    .color(conrod_core::color::WHITE)
+                    .font_size(12)
+                    .left_justify()
+                    .w(list_width - thumbnail_side - (margin * 2.0))
+                    .right_from(thumbnail_id, margin)
+                    .align_middle_y_of(item_id)
+                    .parent(item_id)
+                    .graphics_for(item_id)
+                    .set(ids.level_texts[i], ui);
+            }
+            Event::Selection(i) => {
+                app.selected_level = Some(workshop_items[indices[i]].published_file_id);
+            }
+            _ => {}
+        }
     }
-}
 
-impl From<steamworks::SteamError> for SteamWorkshopSyncError {
-    fn from(e: steamworks::SteamError) -> Self {
-        Self::Steam(e)
+    if let Some(scrollbar) = scrollbar {
+        scrollbar.set(ui);
     }
-}
 
-impl From<std::io::Error> for SteamWorkshopSyncError {
-    fn from(e: std::io::Error) -> Self {
-        Self::Io(e)
+    let level_preview = app
+        .selected_level
+        .and_then(|published_file_id| app.level_preview_images.get(&published_file_id));
+    match level_preview {
+        Some(image_id) => widget::Image::new(*image_id)
+            .w_h(preview_width, preview_height)
+            .right_from(ids.level_list, margin)
+            .align_top_of(ids.level_list)
+            .set(ids.level_preview, ui),
+        None => widget::Rectangle::fill_with(
+            [preview_width, preview_height],
+            conrod_core::color::DARK_CHARCOAL,
+        )
+        .right_from(ids.level_list, margin)
+        .align_top_of(ids.level_list)
+        .set(ids.level_preview, ui),
     }
-}
 
-impl std::fmt::Display for SteamWorkshopSyncError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Recieve(e) => e.fmt(f),
-            Self::Steam(e) => e.fmt(f),
-            Self::Io(e) => e.fmt(f),
+    let selected_item = app.selected_level.and_then(|published_file_id| {
+        workshop_items
+            .iter()
+            .find(|item| item.published_file_id == published_file_id)
+            .map(|item| (published_file_id, item.user_state))
+    });
+    drop(workshop_items);
+
+    if let Some((published_file_id, user_state)) = selected_item {
+        let action_button_width = 120.0;
+
+        let subscribe_action = if user_state.subscribed {
+            ItemAction::Unsubscribe
+        } else {
+            ItemAction::Subscribe
+        };
+        let favorite_action = if user_state.favorited {
+            ItemAction::Unfavorite
+        } else {
+            ItemAction::Favorite
+        };
+
+        let mut item_action = None;
+
+        for () in widget::Button::new()
+            .label(subscribe_action.as_str())
+            .label_font_size(14)
+            .w_h(action_button_width, control_height)
+            .down_from(ids.level_list, margin)
+            .align_left_of(ids.level_list)
+            .set(ids.level_subscribe_button, ui)
+        {
+            item_action = Some(subscribe_action);
+        }
+
+        for () in widget::Button::new()
+            .label(ItemAction::VoteUp.as_str())
+            .label_font_size(14)
+            .w_h(action_button_width, control_height)
+            .right_from(ids.level_subscribe_button, margin)
+            .set(ids.level_vote_up_button, ui)
+        {
+            item_action = Some(ItemAction::VoteUp);
+        }
+
+        for () in widget::Button::new()
+            .label(ItemAction::VoteDown.as_str())
+            .label_font_size(14)
+            .w_h(action_button_width, control_height)
+            .right_from(ids.level_vote_up_button, margin)
+            .set(ids.level_vote_down_button, ui)
+        {
+            item_action = Some(ItemAction::VoteDown);
+        }
 
-            Self::MissingItemInfo => write!(f, "Missing workshop item info"),
+        for () in widget::Button::new()
+            .label(favorite_action.as_str())
+            .label_font_size(14)
+            .w_h(action_button_width, control_height)
+            .right_from(ids.level_vote_down_button, margin)
+            .set(ids.level_favorite_button, ui)
+        {
+            item_action = Some(favorite_action);
+        }
+
+        let in_pack = app.pack_selection.contains(&published_file_id);
+        for () in widget::Button::new()
+            .label(if in_pack {
+                "Remove from Pack"
+            } else {
+                "Add to Pack"
+            })
+            .label_font_size(14)
+            .w_h(action_button_width, control_height)
+            .right_from(ids.level_favorite_button, margin)
+            .set(ids.level_pack_button, ui)
+        {
+            if in_pack {
+                app.pack_selection
+                    .retain(|selected| *selected != published_file_id);
+            } else {
+                app.pack_selection.push(published_file_id);
+            }
+        }
+
+        if let Some(item_action) = item_action {
+            app.perform_item_action(published_file_id, item_action);
         }
     }
 }
 
-impl StdError for SteamWorkshopSyncError {}
+/// The view for publishing a local level to the workshop.
+fn publish_gui(ui: &mut conrod_core::UiCell, ids: &Ids, app: &mut App) {
+    let margin = 10.0;
+    let label_width = 150.0;
+    let control_height = 30.0;
+    let field_width = ui.win_w - label_width - (margin * 3.0);
 
-#[derive(Debug)]
-pub enum SteamWorkshopSyncState {
-    Starting,
+    for () in widget::Button::new()
+        .label("Back")
+        .label_font_size(14)
+        .w_h(100.0, control_height)
+        .top_left_with_margin_on(ui.window, margin)
+        .set(ids.back_button, ui)
+    {
+        app.view = View::Home;
+    }
+
+    let fields = [
+        (
+            ids.publish_level_path_label,
+            ids.publish_level_path,
+            "Level File",
+            &mut app.publish_form.level_path,
+        ),
+        (
+            ids.publish_title_label,
+            ids.publish_title,
+            "Title",
+            &mut app.publish_form.title,
+        ),
+        (
+            ids.publish_description_label,
+            ids.publish_description,
+            "Description",
+            &mut app.publish_form.description,
+        ),
+        (
+            ids.publish_tags_label,
+            ids.publish_tags,
+            "Tags (comma separated)",
+            &mut app.publish_form.tags,
+        ),
+        (
+            ids.publish_preview_path_label,
+            ids.publish_preview_path,
+            "Preview Image",
+            &mut app.publish_form.preview_path,
+        ),
+    ];
+
+    let mut last_field = None;
+    for (label_id, field_id, label, value) in fields {
+        let text_box = widget::TextBox::new(value)
+            .font_size(14)
+            .w_h(field_width, control_height);
+        let text_box = match last_field {
+            Some(last_field) => text_box
+                .down_from(last_field, margin)
+                .align_left_of(last_field),
+            None => text_box.top_left_with_margins_on(
+                ui.window,
+                control_height + (margin * 6.0),
+                label_width + (margin * 2.0),
+            ),
+        };
+        for event in text_box.set(field_id, ui) {
+            if let widget::text_box::Event::Update(new_value) = event {
+                *value = new_value;
+            }
+        }
+
+        widget::Text::new(label)
+            .color(conrod_core::color::WHITE)
+            .font_size(14)
+            .left_from(field_id, margin)
+            .align_middle_y_of(field_id)
+            .set(label_id, ui);
+
+        last_field = Some(field_id);
+    }
+
+    let visibility_labels: Vec<&str> = ItemVisibility::ALL
+        .iter()
+        .map(|visibility| visibility.as_str())
+        .collect();
+    let selected_visibility = ItemVisibility::ALL
+        .iter()
+        .position(|visibility| *visibility == app.publish_form.visibility);
+    for selected in widget::DropDownList::new(&visibility_labels, selected_visibility)
+        .label_font_size(14)
+        .w_h(200.0, control_height)
+        .down_from(ids.publish_preview_path, margin)
+        .align_left_of(ids.publish_preview_path)
+        .set(ids.publish_visibility, ui)
+    {
+        app.publish_form.visibility = ItemVisibility::ALL[selected];
+    }
 
-    InProgress(usize, usize),
+    for () in widget::Button::new()
+        .label("Publish")
+        .label_font_size(14)
+        .w_h(200.0, control_height)
+        .right_from(ids.publish_visibility, margin)
+        .set(ids.publish_submit_button, ui)
+    {
+        app.publish_level();
+    }
 
-    Done,
-    Failed(SteamWorkshopSyncError),
+    upload_status_gui(ui, ids, app, ids.publish_visibility, field_width);
 }
 
-impl SteamWorkshopSyncState {
-    pub fn begin_sync(&mut self, len: usize) {
-        *self = Self::InProgress(0, len);
+/// The view listing the user's published levels, for uploading new versions of them.
+fn my_levels_gui(ui: &mut conrod_core::UiCell, ids: &mut Ids, app: &mut App) {
+    let margin = 10.0;
+    let label_width = 150.0;
+    let control_height = 30.0;
+    let list_item_height = 40.0;
+    let list_width = ui.win_w - (margin * 2.0);
+    let list_height = ui.win_h - 330.0;
+    let field_width = ui.win_w - label_width - (margin * 3.0);
+
+    for () in widget::Button::new()
+        .label("Back")
+        .label_font_size(14)
+        .w_h(100.0, control_height)
+        .top_left_with_margin_on(ui.window, margin)
+        .set(ids.back_button, ui)
+    {
+        app.view = View::Home;
     }
 
-    pub fn add_synced(&mut self, synced: usize) {
-        if let Self::InProgress(old_synced, total) = self {
-            *old_synced += synced;
-            if *old_synced >= *total {
-                *self = Self::Done;
+    let published_items = app.published_items.lock();
+
+    ids.my_level_texts
+        .resize(published_items.len(), &mut ui.widget_id_generator());
+    let selected_index = app.update_form.selected.and_then(|published_file_id| {
+        published_items
+            .iter()
+            .position(|item| item.workshop_item.published_file_id == published_file_id)
+    });
+
+    let (mut events, scrollbar) = widget::ListSelect::single(published_items.len())
+        .flow_down()
+        .item_size(list_item_height)
+        .scrollbar_next_to()
+        .w_h(list_width, list_height)
+        .down_from(ids.title, margin)
+        .align_left_of(ids.back_button)
+        .set(ids.my_levels_list, ui);
+
+    while let Some(event) = events.next(ui, |i| Some(i) == selected_index) {
+        use conrod_core::widget::list_select::Event;
+        match event {
+            Event::Item(list_item) => {
+                let i = list_item.i;
+                let item_id = list_item.widget_id;
+                let published_item = &published_items[i];
+
+                let background_color = if Some(i) == selected_index {
+                    conrod_core::color::DARK_BLUE
+                } else {
+                    conrod_core::color::TRANSPARENT
+                };
+                list_item.set(
+                    widget::Rectangle::fill_with([list_width, list_item_height], background_color),
+                    ui,
+                );
+
+                let level_path = published_item
+                    .level_path
+                    .as_ref()
+                    .map_or(Cow::Borrowed("Unknown"), |path| path.to_string_lossy());
+                let text = format!(
+                    "{} ({})\nLocal File: {}",
+                    published_item.workshop_item.title,
+                    published_item.workshop_item.published_file_id,
+                    level_path
+                );
+                widget::Text::new(&text)
+                    .color(conrod_core::color::WHITE)
+                    .font_size(12)
+                    .left_justify()
+                    .w(list_width - (margin * 2.0))
+                    .mid_left_with_margin_on(item_id, margin)
+                    .parent(item_id)
+                    .graphics_for(item_id)
+                    .set(ids.my_level_texts[i], ui);
+            }
+            Event::Selection(i) => {
+                let published_item = &published_items[i];
+                app.update_form.selected = Some(published_item.workshop_item.published_file_id);
+                app.update_form.level_path = published_item
+                    .level_path
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .unwrap_or_default();
             }
+            _ => {}
         }
     }
 
-    pub fn set_fail(&mut self, e: SteamWorkshopSyncError) {
-        *self = Self::Failed(e);
+    if let Some(scrollbar) = scrollbar {
+        scrollbar.set(ui);
     }
+    drop(published_items);
 
-    pub fn is_syncing(&self) -> bool {
-        matches!(self, Self::Starting | Self::InProgress(_, _))
+    for event in widget::TextBox::new(&app.update_form.level_path)
+        .font_size(14)
+        .w_h(field_width, control_height)
+        .down_from(ids.my_levels_list, margin)
+        .align_right_of(ids.my_levels_list)
+        .set(ids.update_level_path, ui)
+    {
+        if let widget::text_box::Event::Update(level_path) = event {
+            app.update_form.level_path = level_path;
+        }
     }
-}
 
-pub struct App {
-    pub config: Config,
+    widget::Text::new("Level File")
+        .color(conrod_core::color::WHITE)
+        .font_size(14)
+        .left_from(ids.update_level_path, margin)
+        .align_middle_y_of(ids.update_level_path)
+        .set(ids.update_level_path_label, ui);
 
-    cover_image: conrod_core::image::Id,
+    for event in widget::TextBox::new(&app.update_form.change_note)
+        .font_size(14)
+        .w_h(field_width, control_height)
+        .down_from(ids.update_level_path, margin)
+        .align_left_of(ids.update_level_path)
+        .set(ids.update_change_note, ui)
+    {
+        if let widget::text_box::Event::Update(change_note) = event {
+            app.update_form.change_note = change_note;
+        }
+    }
 
-    pub tokio_rt: TokioRuntime,
-    pub steam_client: steamworks::Client,
-    steam_single_client: steamworks::SingleClient,
-    steam_workshop_sync_state: Arc<Mutex<SteamWorkshopSyncState>>,
+    widget::Text::new("Change Notes")
+        .color(conrod_core::color::WHITE)
+        .font_size(14)
+        .left_from(ids.update_change_note, margin)
+        .align_middle_y_of(ids.update_change_note)
+        .set(ids.update_change_note_label, ui);
+
+    for () in widget::Button::new()
+        .label("Submit Update")
+        .label_font_size(14)
+        .w_h(200.0, control_height)
+        .down_from(ids.update_change_note, margin)
+        .align_left_of(ids.update_change_note)
+        .set(ids.update_submit_button, ui)
+    {
+        app.update_level();
+    }
+
+    upload_status_gui(ui, ids, app, ids.update_submit_button, field_width);
 }
 
-impl App {
-    pub fn new(config: Config, cover_image: conrod_core::image::Id) -> Result<Self, AppError> {
-        let tokio_rt = TokioRuntime::new()?;
+/// The local library view, for managing favorites, tags, notes and playlists of level files.
+fn library_gui(ui: &mut conrod_core::UiCell, ids: &mut Ids, app: &mut App) {
+    let margin = 10.0;
+    let control_height = 30.0;
+    let label_width = 60.0;
+    let list_item_height = 40.0;
+    let playlist_item_height = 25.0;
+    let column_width = (ui.win_w - (margin * 3.0)) / 2.0;
+    let list_height = ui.win_h - 200.0;
+    let playlist_height = list_height - 300.0;
+
+    for () in widget::Button::new()
+        .label("Back")
+        .label_font_size(14)
+        .w_h(100.0, control_height)
+        .top_left_with_margin_on(ui.window, margin)
+        .set(ids.back_button, ui)
+    {
+        app.view = View::Home;
+    }
 
-        // For now, lets make steamworks necessary.
-        let (steam_client, steam_single_client) = steamworks::Client::init()?;
+    for favorites_only in widget::Toggle::new(app.library_form.favorites_only)
+        .label("Favorites Only")
+        .label_font_size(14)
+        .w_h(150.0, control_height)
+        .right_from(ids.back_button, margin)
+        .set(ids.library_favorites_only_toggle, ui)
+    {
+        app.library_form.favorites_only = favorites_only;
+    }
+
+    for () in widget::Button::new()
+        .label("Refresh")
+        .label_font_size(14)
+        .w_h(100.0, control_height)
+        .right_from(ids.library_favorites_only_toggle, margin)
+        .set(ids.library_refresh_button, ui)
+    {
+        app.refresh_library();
+    }
 
-        let ugc_query_future = UgcQueryBuilder::new(&steam_client)
-            .user_list(steamworks::UserList::Subscribed)
-            .send(|res| res.map(|res| res.iter().collect::<Vec<_>>()))?;
+    // Levels
 
-        let sync_dir = config.get_workshop_sync_path().clone();
-        if !sync_dir.exists() {
-            std::fs::create_dir_all(&sync_dir)?;
+    let levels: Vec<&PathBuf> = app
+        .library_levels
+        .iter()
+        .filter(|path| !app.library_form.favorites_only || app.library.get_info(path).favorite)
+        .collect();
+    ids.library_level_texts
+        .resize(levels.len(), &mut ui.widget_id_generator());
+    let selected_index = app
+        .library_form
+        .selected_level
+        .as_ref()
+        .and_then(|selected_level| levels.iter().position(|path| *path == selected_level));
+
+    let (mut events, scrollbar) = widget::ListSelect::single(levels.len())
+        .flow_down()
+        .item_size(list_item_height)
+        .scrollbar_next_to()
+        .w_h(column_width, list_height)
+        .down_from(ids.title, margin)
+        .align_left_of(ids.back_button)
+        .set(ids.library_list, ui);
+
+    let mut selected_level = None;
+    while let Some(event) = events.next(ui, |i| Some(i) == selected_index) {
+        use conrod_core::widget::list_select::Event;
+        match event {
+            Event::Item(list_item) => {
+                let i = list_item.i;
+                let item_id = list_item.widget_id;
+                let path = levels[i];
+                let info = app.library.get_info(path);
+
+                let background_color = if Some(i) == selected_index {
+                    conrod_core::color::DARK_BLUE
+                } else {
+                    conrod_core::color::TRANSPARENT
+                };
+                list_item.set(
+                    widget::Rectangle::fill_with(
+                        [column_width, list_item_height],
+                        background_color,
+                    ),
+                    ui,
+                );
+
+                let mut text = get_level_name(path).into_owned();
+                if info.favorite {
+                    text.push_str(" [Favorite]");
+                }
+                if !info.tags.is_empty() {
+                    text.push_str(&format!(" [{}]", info.tags.join(", ")));
+                }
+                if let Some(parent) = path.parent() {
+                    text.push_str(&format!("\n{}", parent.display()));
+                }
+                widget::Text::new(&text)
+                    .color(conrod_core::color::WHITE)
+                    .font_size(12)
+                    .left_justify()
+                    .w(column_width - (margin * 2.0))
+                    .mid_left_with_margin_on(item_id, margin)
+                    .parent(item_id)
+                    .graphics_for(item_id)
+                    .set(ids.library_level_texts[i], ui);
+            }
+            Event::Selection(i) => selected_level = Some(levels[i].clone()),
+            _ => {}
         }
+    }
+
+    if let Some(scrollbar) = scrollbar {
+        scrollbar.set(ui);
+    }
+    if let Some(selected_level) = selected_level {
+        app.select_library_level(selected_level);
+    }
 
-        if !sync_dir.is_dir() {
-            return Err(AppError::InvalidSyncDir);
+    for event in widget::TextBox::new(&app.library_form.folder)
+        .font_size(14)
+        .w_h(column_width - 120.0, control_height)
+        .down_from(ids.library_list, margin)
+        .align_left_of(ids.library_list)
+        .set(ids.library_folder, ui)
+    {
+        if let widget::text_box::Event::Update(folder) = event {
+            app.library_form.folder = folder;
         }
+    }
+
+    for () in widget::Button::new()
+        .label("Add Folder")
+        .label_font_size(14)
+        .w_h(110.0, control_height)
+        .right_from(ids.library_folder, margin)
+        .set(ids.library_add_folder_button, ui)
+    {
+        app.add_library_folder();
+    }
 
-        let steam_workshop_sync_state = Arc::new(Mutex::new(SteamWorkshopSyncState::Starting));
-        let steam_workshop_sync_state_clone = steam_workshop_sync_state.clone();
+    // Selected level
 
-        let steam_client_clone = steam_client.clone();
+    widget::Rectangle::fill_with([column_width, list_height], conrod_core::color::TRANSPARENT)
+        .right_from(ids.library_list, margin)
+        .align_top_of(ids.library_list)
+        .set(ids.library_details, ui);
 
-        tokio_rt.spawn(async move {
-            if let Err(e) = sync_steam_workshop(
-                steam_client_clone,
-                ugc_query_future.await,
-                steam_workshop_sync_state_clone.clone(),
-                sync_dir,
-            )
-            .await
-            {
-                eprintln!("Sync Failed: {}", e);
-                steam_workshop_sync_state_clone.lock().set_fail(e);
+    let level_name = match app.library_form.selected_level.as_ref() {
+        Some(path) => get_level_name(path),
+        None => "No level selected".into(),
+    };
+    widget::Text::new(&level_name)
+        .color(conrod_core::color::WHITE)
+        .font_size(18)
+        .top_left_of(ids.library_details)
+        .set(ids.library_level_name, ui);
+
+    let selected_info = app
+        .library_form
+        .selected_level
+        .as_ref()
+        .map(|path| app.library.get_info(path));
+    if let Some(info) = selected_info {
+        for favorite in widget::Toggle::new(info.favorite)
+            .label("Favorite")
+            .label_font_size(14)
+            .w_h(120.0, control_height)
+            .down_from(ids.library_level_name, margin)
+            .align_left_of(ids.library_details)
+            .set(ids.library_favorite_toggle, ui)
+        {
+            app.set_library_favorite(favorite);
+        }
+
+        for () in widget::Button::new()
+            .label("Add to Playlist")
+            .label_font_size(14)
+            .w_h(150.0, control_height)
+            .right_from(ids.library_favorite_toggle, margin)
+            .set(ids.library_add_to_playlist_button, ui)
+        {
+            app.add_to_playlist();
+        }
+
+        for event in widget::TextBox::new(&app.library_form.tags)
+            .font_size(14)
+            .w_h(column_width - label_width - margin, control_height)
+            .down_from(ids.library_favorite_toggle, margin)
+            .align_right_of(ids.library_details)
+            .set(ids.library_tags, ui)
+        {
+            if let widget::text_box::Event::Update(tags) = event {
+                app.library_form.tags = tags;
+                app.update_library_level_info();
             }
-        });
+        }
 
-        Ok(App {
-            config,
+        widget::Text::new("Tags")
+            .color(conrod_core::color::WHITE)
+            .font_size(14)
+            .left_from(ids.library_tags, margin)
+            .align_middle_y_of(ids.library_tags)
+            .set(ids.library_tags_label, ui);
 
-            cover_image,
+        for event in widget::TextBox::new(&app.library_form.notes)
+            .font_size(14)
+            .w_h(column_width - label_width - margin, control_height)
+            .down_from(ids.library_tags, margin)
+            .align_right_of(ids.library_details)
+            .set(ids.library_notes, ui)
+        {
+            if let widget::text_box::Event::Update(notes) = event {
+                app.library_form.notes = notes;
+                app.update_library_level_info();
+            }
+        }
 
-            tokio_rt,
-            steam_client,
-            steam_single_client,
-            steam_workshop_sync_state,
-        })
+        widget::Text::new("Notes")
+            .color(conrod_core::color::WHITE)
+            .font_size(14)
+            .left_from(ids.library_notes, margin)
+            .align_middle_y_of(ids.library_notes)
+            .set(ids.library_notes_label, ui);
     }
 
-    pub fn update(&mut self) {
-        self.steam_single_client.run_callbacks();
+    // Playlists
+
+    for event in widget::TextBox::new(&app.library_form.playlist_name)
+        .font_size(14)
+        .w_h(200.0, control_height)
+        .top_left_with_margins_on(ids.library_details, 170.0, 0.0)
+        .set(ids.library_playlist_name, ui)
+    {
+        if let widget::text_box::Event::Update(playlist_name) = event {
+            app.library_form.playlist_name = playlist_name;
+        }
     }
-}
 
-async fn sync_steam_workshop(
-    steam_client: steamworks::Client,
-    workshop_data: Result<
-        Result<Vec<steamworks::QueryResult>, steamworks::SteamError>,
-        OneShotRecvError,
-    >,
-    steam_workshop_sync_state: Arc<Mutex<SteamWorkshopSyncState>>,
-    mut sync_dir: PathBuf,
-) -> Result<(), SteamWorkshopSyncError> {
-    let workshop_data = workshop_data??;
+    for () in widget::Button::new()
+        .label("New Playlist")
+        .label_font_size(14)
+        .w_h(column_width - 200.0 - margin, control_height)
+        .right_from(ids.library_playlist_name, margin)
+        .set(ids.library_new_playlist_button, ui)
+    {
+        app.create_playlist();
+    }
+
+    for () in widget::Button::new()
+        .label("Delete")
+        .label_font_size(14)
+        .w_h(column_width - 200.0 - margin, control_height)
+        .down_from(ids.library_new_playlist_button, margin)
+        .align_left_of(ids.library_new_playlist_button)
+        .set(ids.library_delete_playlist_button, ui)
+    {
+        app.delete_playlist();
+    }
+
+    let playlist_names: Vec<&str> = app
+        .library
+        .playlists
+        .iter()
+        .map(|playlist| playlist.name.as_str())
+        .collect();
+    for selected in widget::DropDownList::new(&playlist_names, app.library_form.selected_playlist)
+        .label_font_size(14)
+        .w_h(200.0, control_height)
+        .down_from(ids.library_playlist_name, margin)
+        .align_left_of(ids.library_playlist_name)
+        .set(ids.library_playlist, ui)
+    {
+        app.library_form.selected_playlist = Some(selected);
+        app.library_form.selected_playlist_level = None;
+    }
+
+    let playlist_levels: Vec<PathBuf> = app
+        .library_form
+        .selected_playlist
+        .and_then(|i| app.library.playlists.get(i))
+        .map(|playlist| playlist.levels.clone())
+        .unwrap_or_default();
+    ids.library_playlist_level_texts
+        .resize(playlist_levels.len(), &mut ui.widget_id_generator());
+    let selected_playlist_level = app.library_form.selected_playlist_level;
+
+    let (mut events, scrollbar) = widget::ListSelect::single(playlist_levels.len())
+        .flow_down()
+        .item_size(playlist_item_height)
+        .scrollbar_next_to()
+        .w_h(column_width, playlist_height)
+        .down_from(ids.library_playlist, margin)
+        .align_left_of(ids.library_playlist)
+        .set(ids.library_playlist_levels, ui);
 
-    steam_workshop_sync_state
-        .lock()
-        .begin_sync(workshop_data.len());
+    while let Some(event) = events.next(ui, |i| Some(i) == selected_playlist_level) {
+        use conrod_core::widget::list_select::Event;
+        match event {
+            Event::Item(list_item) => {
+                let i = list_item.i;
+                let item_id = list_item.widget_id;
 
-    for workshop_item in workshop_data.iter() {
-        let item_info = steam_client
-            .ugc()
-            .item_install_info(workshop_item.published_file_id)
-            .ok_or(SteamWorkshopSyncError::MissingItemInfo)?;
+                let background_color = if Some(i) == selected_playlist_level {
+                    conrod_core::color::DARK_BLUE
+                } else {
+                    conrod_core::color::TRANSPARENT
+                };
+                list_item.set(
+                    widget::Rectangle::fill_with(
+                        [column_width, playlist_item_height],
+                        background_color,
+                    ),
+                    ui,
+                );
 
-        sync_dir.push(&workshop_item.title);
-        sync_dir.set_extension("txt");
-        tokio::fs::copy(&item_info.folder, &sync_dir).await?;
-        sync_dir.pop();
+                let text = format!("{}. {}", i + 1, get_level_name(&playlist_levels[i]));
+                widget::Text::new(&text)
+                    .color(conrod_core::color::WHITE)
+                    .font_size(12)
+                    .left_justify()
+                    .w(column_width - (margin * 2.0))
+                    .mid_left_with_margin_on(item_id, margin)
+                    .parent(item_id)
+                    .graphics_for(item_id)
+                    .set(ids.library_playlist_level_texts[i], ui);
+            }
+            Event::Selection(i) => app.library_form.selected_playlist_level = Some(i),
+            _ => {}
+        }
+    }
+
+    if let Some(scrollbar) = scrollbar {
+        scrollbar.set(ui);
+    }
 
-        steam_workshop_sync_state.lock().add_synced(1);
+    let playlist_button_width = (column_width - (margin * 2.0)) / 3.0;
+    for () in widget::Button::new()
+        .label("Move Up")
+        .label_font_size(14)
+        .w_h(playlist_button_width, control_height)
+        .down_from(ids.library_playlist_levels, margin)
+        .align_left_of(ids.library_playlist_levels)
+        .set(ids.library_move_up_button, ui)
+    {
+        app.move_playlist_level(true);
     }
 
-    Ok(())
+    for () in widget::Button::new()
+        .label("Move Down")
+        .label_font_size(14)
+        .w_h(playlist_button_width, control_height)
+        .right_from(ids.library_move_up_button, margin)
+        .set(ids.library_move_down_button, ui)
+    {
+        app.move_playlist_level(false);
+    }
+
+    for () in widget::Button::new()
+        .label("Remove")
+        .label_font_size(14)
+        .w_h(playlist_button_width, control_height)
+        .right_from(ids.library_move_down_button, margin)
+        .set(ids.library_remove_button, ui)
+    {
+        app.remove_playlist_level();
+    }
+
+    for event in widget::TextBox::new(&app.library_form.export_dir)
+        .font_size(14)
+        .w_h(column_width - 150.0 - margin, control_height)
+        .down_from(ids.library_details, margin)
+        .align_left_of(ids.library_details)
+        .set(ids.library_export_dir, ui)
+    {
+        if let widget::text_box::Event::Update(export_dir) = event {
+            app.library_form.export_dir = export_dir;
+        }
+    }
+
+    for () in widget::Button::new()
+        .label("Export Playlist")
+        .label_font_size(14)
+        .w_h(150.0, control_height)
+        .right_from(ids.library_export_dir, margin)
+        .set(ids.library_export_button, ui)
+    {
+        app.export_playlist();
+    }
+}
+
+/// Shows the state of the current publish or update, below the given widget.
+fn upload_status_gui(
+    ui: &mut conrod_core::UiCell,
+    ids: &Ids,
+    app: &App,
+    below: widget::Id,
+    width: f64,
+) {
+    let margin = 10.0;
+    let control_height = 30.0;
+
+    let (status, progress) = match app.publish_state.as_ref() {
+        Some(publish_state) => match &*publish_state.lock() {
+            PublishState::Creating => ("Creating workshop item...".into(), None),
+            PublishState::Staging => ("Copying level...".into(), None),
+            PublishState::Uploading(watcher) => {
+                let (status, processed, total) = watcher.progress();
+                let progress = if total > 0 {
+                    Some(processed as f64 / total as f64)
+                } else {
+                    None
+                };
+                (format!("{}...", status.as_str()), progress)
+            }
+            PublishState::Done(published_file_id, _) => {
+                (format!("Uploaded item {}", published_file_id), None)
+            }
+            PublishState::Failed(e) => (format!("Upload failed: {}", e), None),
+        },
+        None => (String::new(), None),
+    };
+
+    widget::Text::new(&status)
+        .color(conrod_core::color::WHITE)
+        .font_size(14)
+        .down_from(below, margin)
+        .align_left_of(below)
+        .set(ids.publish_status, ui);
+
+    if let Some(progress) = progress {
+        widget::Rectangle::fill_with(
+            [width, control_height / 2.0],
+            conrod_core::color::DARK_CHARCOAL,
+        )
+        .down_from(ids.publish_status, margin)
+        .align_left_of(ids.publish_status)
+        .set(ids.publish_progress_background, ui);
+
+        widget::Rectangle::fill_with(
+            [width * progress.min(1.0), control_height / 2.0],
+            conrod_core::color::LIGHT_BLUE,
+        )
+        .top_left_of(ids.publish_progress_background)
+        .set(ids.publish_progress, ui);
+    }
+}
+
+/// Formats a number of bytes with a binary unit, like "1.5 MiB".
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in UNITS.iter().skip(1) {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+
+    format!("{:.1} {}", size, unit)
+}
+
+/// Formats a workshop item as the text of a level list entry.
+fn format_workshop_item(workshop_item: &WorkshopItem) -> String {
+    const MAX_DESCRIPTION_CHARS: usize = 100;
+
+    let updated = chrono::DateTime::<chrono::Local>::from(
+        std::time::UNIX_EPOCH + Duration::from_secs(workshop_item.time_updated.into()),
+    );
+
+    let mut description: String = workshop_item
+        .description
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let Some((i, _)) = description.char_indices().nth(MAX_DESCRIPTION_CHARS) {
+        description.truncate(i);
+        description.push_str("...");
+    }
+
+    let tags = if workshop_item.tags.is_empty() {
+        "None".into()
+    } else {
+        workshop_item.tags.join(", ")
+    };
+
+    let mut status = String::new();
+    let user_state = workshop_item.user_state;
+    if user_state.subscribed {
+        status.push_str(" [Subscribed]");
+    }
+    match user_state.vote {
+        Some(true) => status.push_str(" [Voted Up]"),
+        Some(false) => status.push_str(" [Voted Down]"),
+        None => {}
+    }
+    if user_state.favorited {
+        status.push_str(" [Favorite]");
+    }
+    if workshop_item.is_stale {
+        status.push_str(" [Stale]");
+    }
+    if let Some(invalid_reason) = workshop_item.invalid_reason.as_ref() {
+        status.push_str(&format!(" [Invalid: {}]", invalid_reason));
+    }
+
+    format!(
+        "{} by {}{}\nScore: {:.2} (+{}/-{}) | Updated: {} | Tags: {}\n{}",
+        workshop_item.title,
+        workshop_item.author,
+        status,
+        workshop_item.score,
+        workshop_item.num_upvotes,
+        workshop_item.num_downvotes,
+        updated.format("%Y-%m-%d"),
+        tags,
+        description
+    )
+}
+
+/// The screen that is currently shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Home,
+    Levels,
+    Publish,
+    MyLevels,
+    Library,
+}
+
+/// The user's input for the publish view.
+#[derive(Debug)]
+pub struct PublishForm {
+    pub level_path: String,
+    pub title: String,
+    pub description: String,
+    pub tags: String,
+    pub preview_path: String,
+    pub visibility: ItemVisibility,
+}
+
+impl PublishForm {
+    /// Makes a request from the form. Empty fields are left unset.
+    fn to_request(&self) -> PublishRequest {
+        let preview_path = self.preview_path.trim();
+
+        PublishRequest {
+            level_path: PathBuf::from(self.level_path.trim()),
+            title: self.title.trim().into(),
+            description: self.description.clone(),
+            tags: self
+                .tags
+                .split(',')
+                .map(|tag| tag.trim())
+                .filter(|tag| !tag.is_empty())
+                .map(String::from)
+                .collect(),
+            visibility: self.visibility,
+            preview_path: if preview_path.is_empty() {
+                None
+            } else {
+                Some(PathBuf::from(preview_path))
+            },
+        }
+    }
+}
+
+/// The user's input for updating a published level.
+#[derive(Debug, Default)]
+pub struct UpdateForm {
+    pub selected: Option<u64>,
+    pub level_path: String,
+    pub change_note: String,
+}
+
+/// The user's input for the library view.
+#[derive(Debug, Default)]
+pub struct LibraryForm {
+    pub selected_level: Option<PathBuf>,
+    pub favorites_only: bool,
+
+    /// The tags of the selected level, comma separated.
+    pub tags: String,
+    pub notes: String,
+
+    /// A folder to add to the library.
+    pub folder: String,
+
+    pub selected_playlist: Option<usize>,
+    pub selected_playlist_level: Option<usize>,
+
+    /// The name of a playlist to create.
+    pub playlist_name: String,
+
+    /// The folder to export the selected playlist to.
+    pub export_dir: String,
+}
+
+impl Default for PublishForm {
+    fn default() -> Self {
+        Self {
+            level_path: String::new(),
+            title: String::new(),
+            description: String::new(),
+            tags: String::new(),
+            preview_path: String::new(),
+            visibility: ItemVisibility::Public,
+        }
+    }
+}
+
+/// A short-lived message shown at the bottom of the window.
+#[derive(Debug)]
+pub struct Toast {
+    pub message: String,
+    pub is_error: bool,
+    expires: Instant,
+}
+
+impl Toast {
+    /// Makes a new toast that expires after TOAST_DURATION.
+    pub fn new(message: String, is_error: bool) -> Self {
+        Self {
+            message,
+            is_error,
+            expires: Instant::now() + TOAST_DURATION,
+        }
+    }
+
+    /// Returns true if this toast should no longer be shown.
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires
+    }
+}
+
+/// The result of library work done in the background.
+enum LibraryTaskResult {
+    Scanned(Result<Vec<PathBuf>, std::io::Error>),
+    Saved(Result<(), LibraryError>),
+    Exported {
+        name: String,
+        export_dir: PathBuf,
+        result: Result<Vec<PathBuf>, LibraryError>,
+    },
+}
+
+/// The result of pack work done in the background.
+enum PackTaskResult {
+    Exported {
+        path: PathBuf,
+
+        /// The items that were in the pack selection when the export started.
+        published_file_ids: Vec<u64>,
+        result: Result<PackManifest, PackError>,
+    },
+    Imported {
+        path: PathBuf,
+        result: Result<Vec<PathBuf>, PackError>,
+    },
+}
+
+/// Runs blocking pack work on the blocking threadpool.
+async fn spawn_pack_task<T, F>(f: F) -> Result<T, PackError>
+where
+    F: FnOnce() -> Result<T, PackError> + Send + 'static,
+    T: Send + 'static,
+{
+    // JoinError is not Sync, so only its message is kept.
+    tokio::task::spawn_blocking(f).await.unwrap_or_else(|e| {
+        Err(PackError::Io(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            e.to_string(),
+        )))
+    })
+}
+
+/// The result of an ItemAction, sent back from the task that waited for steam.
+struct CompletedItemAction {
+    published_file_id: u64,
+    action: ItemAction,
+
+    /// The user state before the action was applied, restored if it failed.
+    previous_user_state: WorkshopItemUserState,
+    result: Result<Result<(), steamworks::SteamError>, OneShotRecvError>,
+}
+
+#[derive(Debug)]
+pub enum AppError {
+    Io(std::io::Error),
+    SteamWorkshopQuery(crate::steamworks_util::WorkshopQueryError),
+    SteamWorkshopSync(SteamWorkshopSyncError),
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> AppError {
+        AppError::Io(e)
+    }
+}
+
+impl From<crate::steamworks_util::WorkshopQueryError> for AppError {
+    fn from(e: crate::steamworks_util::WorkshopQueryError) -> AppError {
+        AppError::SteamWorkshopQuery(e)
+    }
+}
+
+impl From<SteamWorkshopSyncError> for AppError {
+    fn from(e: SteamWorkshopSyncError) -> AppError {
+        AppError::SteamWorkshopSync(e)
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => e.fmt(f),
+            Self::SteamWorkshopQuery(e) => e.fmt(f),
+            Self::SteamWorkshopSync(e) => e.fmt(f),
+        }
+    }
+}
+
+pub struct App {
+    pub config: Config,
+    view: View,
+    config_watcher: Option<ConfigWatcher>,
+    toast: Option<Toast>,
+    pending_crash_report: Option<PathBuf>,
+
+    gpu_info: GpuInfo,
+    redact_diagnostics: bool,
+
+    workshop_items: Arc<Mutex<Vec<WorkshopItem>>>,
+    preview_cache: Arc<PreviewCache>,
+    requested_previews: HashSet<u64>,
+    decoded_previews: Arc<Mutex<Vec<(u64, RgbaImage)>>>,
+    preview_images: HashMap<u64, conrod_core::image::Id>,
+
+    /// The versions of levels whose previews were requested, by published file id and time updated.
+    requested_level_previews: HashSet<(u64, u32)>,
+
+    /// Requested level previews whose version was not synced yet. They are requested again after the next sync.
+    unsynced_level_previews: Arc<Mutex<Vec<(u64, u32)>>>,
+    rendered_level_previews: Arc<Mutex<Vec<(u64, RgbaImage)>>>,
+    level_preview_images: HashMap<u64, conrod_core::image::Id>,
+    level_filter: String,
+    level_sort: WorkshopItemSort,
+    selected_level: Option<u64>,
+
+    /// The items to export with the next pack, in the order they were added.
+    pack_selection: Vec<u64>,
+    completed_item_actions: Arc<Mutex<Vec<CompletedItemAction>>>,
+    publish_form: PublishForm,
+    publish_state: Option<Arc<Mutex<PublishState>>>,
+    reported_publish: bool,
+    published_items: Arc<Mutex<Vec<PublishedItem>>>,
+    update_form: UpdateForm,
+    library: Library,
+    library_levels: Vec<PathBuf>,
+    library_form: LibraryForm,
+
+    /// When to save the library, if it changed since the last save.
+    library_save_at: Option<Instant>,
+    is_saving_library: bool,
+    completed_library_tasks: Arc<Mutex<Vec<LibraryTaskResult>>>,
+    completed_pack_tasks: Arc<Mutex<Vec<PackTaskResult>>>,
+
+    cover_image: conrod_core::image::Id,
+
+    pub tokio_rt: TokioRuntime,
+    pub steam_client: Option<steamworks::Client>,
+    steam_single_client: Option<steamworks::SingleClient>,
+    call_result_poller: Option<CallResultPoller>,
+    last_steam_init: Instant,
+
+    /// Keeps the item callbacks registered while steam is connected.
+    item_callbacks: Vec<steamworks::CallbackHandle>,
+
+    /// Items steam installed or updated since the last update.
+    changed_items: Arc<Mutex<Vec<u64>>>,
+    steam_workshop_sync_state: Arc<Mutex<SteamWorkshopSyncState>>,
+    sync_requested: bool,
+    last_sync: Instant,
+    was_syncing: bool,
+}
+
+impl App {
+    pub fn new(
+        config: Config,
+        config_path: PathBuf,
+        gpu_info: GpuInfo,
+        cover_image: conrod_core::image::Id,
+    ) -> Result<Self, AppError> {
+        let tokio_rt = TokioRuntime::new()?;
+
+        // Hot-reloading is a convenience, don't fail if it can't be set up.
+        let config_watcher = match ConfigWatcher::new(config_path) {
+            Ok(config_watcher) => Some(config_watcher),
+            Err(e) => {
+                warn!("Failed to watch config: {}", e);
+                None
+            }
+        };
+
+        let sync_dir = config.get_workshop_sync_path().clone();
+        ensure_sync_dir(&sync_dir)?;
+
+        let pending_crash_report = crate::crash::get_pending_crash_report(config.get_data_dir());
+        if let Some(path) = pending_crash_report.as_ref() {
+            warn!("Found crash report from last run: '{}'", path.display());
+        }
+
+        let preview_fetcher: Box<dyn Fetcher> = match config.preview_source_dir.as_ref() {
+            Some(preview_source_dir) => Box::new(FileFetcher::new(preview_source_dir.clone())),
+            None => Box::new(HttpFetcher::default()),
+        };
+        let preview_cache = PreviewCache::new(
+            PreviewCache::get_cache_dir(config.get_data_dir()),
+            preview_fetcher,
+        );
+
+        // Show the last known items right away, the sync replaces them once steam responds.
+        let workshop_cache = match WorkshopCache::load(config.get_data_dir()) {
+            Ok(workshop_cache) => workshop_cache,
+            Err(e) => {
+                warn!("Failed to load workshop cache: {}", e);
+                WorkshopCache::default()
+            }
+        };
+
+        let library = match Library::load(config.get_data_dir()) {
+            Ok(library) => library,
+            Err(e) => {
+                warn!("Failed to load library: {}", e);
+                Library::default()
+            }
+        };
+
+        let mut app = App {
+            config,
+            view: View::Home,
+            config_watcher,
+            toast: None,
+            pending_crash_report,
+
+            gpu_info,
+            redact_diagnostics: true,
+
+            workshop_items: Arc::new(Mutex::new(workshop_cache.items)),
+            preview_cache: Arc::new(preview_cache),
+            requested_previews: HashSet::new(),
+            decoded_previews: Arc::default(),
+            preview_images: HashMap::new(),
+            requested_level_previews: HashSet::new(),
+            unsynced_level_previews: Arc::default(),
+            rendered_level_previews: Arc::default(),
+            level_preview_images: HashMap::new(),
+            level_filter: String::new(),
+            level_sort: WorkshopItemSort::Title,
+            selected_level: None,
+            pack_selection: Vec::new(),
+            completed_item_actions: Arc::default(),
+            publish_form: PublishForm::default(),
+            publish_state: None,
+            reported_publish: false,
+            published_items: Arc::default(),
+            update_form: UpdateForm::default(),
+            library,
+            library_levels: Vec::new(),
+            library_form: LibraryForm::default(),
+            library_save_at: None,
+            is_saving_library: false,
+            completed_library_tasks: Arc::default(),
+            completed_pack_tasks: Arc::default(),
+
+            cover_image,
+
+            tokio_rt,
+            steam_client: None,
+            steam_single_client: None,
+            call_result_poller: None,
+            last_steam_init: Instant::now(),
+            item_callbacks: Vec::new(),
+            changed_items: Arc::default(),
+            steam_workshop_sync_state: Arc::new(Mutex::new(SteamWorkshopSyncState::Offline)),
+            sync_requested: false,
+            last_sync: Instant::now(),
+            was_syncing: false,
+        };
+
+        crate::crash::set_steam_workshop_sync_state(app.steam_workshop_sync_state.clone());
+
+        // Launching the game does not need steam, so start offline if it is not available.
+        app.init_steam()?;
+
+        Ok(app)
+    }
+
+    /// Tries to connect to steam, starting a workshop sync if successful.
+    fn init_steam(&mut self) -> Result<(), AppError> {
+        self.last_steam_init = Instant::now();
+
+        let (steam_client, steam_single_client) = match steamworks::Client::init() {
+            Ok(clients) => clients,
+            Err(e) => {
+                warn!("Failed to init steam, running offline: {}", e);
+                return Ok(());
+            }
+        };
+
+        info!("Connected to steam");
+
+        self.call_result_poller = Some(CallResultPoller::new(&steam_client));
+        self.register_item_callbacks(&steam_client);
+        self.steam_client = Some(steam_client);
+        self.steam_single_client = Some(steam_single_client);
+
+        self.start_sync()
+    }
+
+    /// Starts a workshop sync. Steam must be connected.
+    fn start_sync(&mut self) -> Result<(), AppError> {
+        let steam_client = match self.steam_client.as_ref() {
+            Some(steam_client) => steam_client,
+            None => return Ok(()),
+        };
+
+        // Failed syncs count too, so they are not retried every frame.
+        self.last_sync = Instant::now();
+
+        let sync_dir = self.config.get_workshop_sync_path().clone();
+        ensure_sync_dir(&sync_dir)?;
+
+        self.steam_workshop_sync_state = spawn_steam_workshop_sync(
+            &self.tokio_rt,
+            steam_client,
+            sync_dir,
+            self.config.get_data_dir().clone(),
+            &self.config.sync_lists,
+            &self.config.sync,
+            self.workshop_items.clone(),
+        )?;
+        crate::crash::set_steam_workshop_sync_state(self.steam_workshop_sync_state.clone());
+        self.sync_requested = false;
+
+        Ok(())
+    }
+
+    /// Registers for steam's notices about installed and downloaded items, so they are synced without a restart.
+    fn register_item_callbacks(&mut self, steam_client: &steamworks::Client) {
+        let app_id = steam_client.utils().app_id();
+
+        let changed_items = self.changed_items.clone();
+        let item_installed = steam_client.register_callback(move |item: ItemInstalled| {
+            if item.app_id == app_id {
+                changed_items.lock().push(item.published_file_id.0);
+            }
+        });
+
+        let changed_items = self.changed_items.clone();
+        let download_item_result =
+            steam_client.register_callback(move |result: DownloadItemResult| {
+                if result.app_id != app_id {
+                    return;
+                }
+
+                match result.error {
+                    Some(e) => warn!("Failed to download {}: {}", result.published_file_id.0, e),
+                    None => changed_items.lock().push(result.published_file_id.0),
+                }
+            });
+
+        self.item_callbacks = vec![item_installed, download_item_result];
+    }
+
+    /// Requests a sync if steam installed or updated items, or if the resync interval passed.
+    fn request_resync(&mut self) {
+        let changed_items = std::mem::take(&mut *self.changed_items.lock());
+        if !changed_items.is_empty() {
+            info!(
+                "Steam installed or updated {} workshop item(s), resyncing",
+                changed_items.len()
+            );
+            self.sync_requested = true;
+        }
+
+        if let Some(resync_interval) = self.config.sync.get_resync_interval() {
+            if !self.is_offline() && self.last_sync.elapsed() >= resync_interval {
+                self.sync_requested = true;
+            }
+        }
+    }
+
+    /// Returns true if steam could not be initialized.
+    pub fn is_offline(&self) -> bool {
+        self.steam_client.is_none()
+    }
+
+    pub fn update(&mut self) {
+        if let Some(steam_single_client) = self.steam_single_client.as_ref() {
+            steam_single_client.run_callbacks();
+            if let Some(call_result_poller) = self.call_result_poller.as_ref() {
+                call_result_poller.poll();
+            }
+        } else if self.last_steam_init.elapsed() >= STEAM_RETRY_INTERVAL {
+            match self.init_steam() {
+                Ok(()) if !self.is_offline() => {
+                    self.show_toast("Connected to Steam".into(), false);
+                }
+                Ok(()) => {}
+                Err(e) => {
+                    error!("Failed to start sync: {}", e);
+                    self.show_toast(format!("Failed to start sync: {}", e), true);
+                }
+            }
+        }
+
+        self.reload_config();
+        self.request_previews();
+        self.request_level_preview();
+        self.finish_item_actions();
+        self.report_publish();
+        self.finish_library_tasks();
+        self.finish_pack_tasks();
+        self.save_library_if_due();
+        self.request_resync();
+
+        // Levels that were not synced when their preview was requested may be now.
+        let is_syncing = self.steam_workshop_sync_state.lock().is_syncing();
+        if self.was_syncing && !is_syncing {
+            for key in self.unsynced_level_previews.lock().drain(..) {
+                self.requested_level_previews.remove(&key);
+            }
+        }
+        self.was_syncing = is_syncing;
+
+        // Only one sync can run at a time, so wait for the current one to finish.
+        if self.sync_requested && !self.steam_workshop_sync_state.lock().is_syncing() {
+            if let Err(e) = self.start_sync() {
+                error!("Failed to start sync: {}", e);
+                self.show_toast(format!("Failed to start sync: {}", e), true);
+                self.sync_requested = false;
+            }
+        }
+
+        if self
+            .toast
+            .as_ref()
+            .map_or(false, |toast| toast.is_expired())
+        {
+            self.toast = None;
+        }
+    }
+
+    /// Starts loading thumbnails for workshop items that don't have one yet.
+    fn request_previews(&mut self) {
+        let workshop_items = self.workshop_items.lock();
+        for workshop_item in workshop_items.iter() {
+            let url = match workshop_item.preview_url.as_ref() {
+                Some(url) if !url.is_empty() => url.clone(),
+                _ => continue,
+            };
+
+            let published_file_id = workshop_item.published_file_id;
+            if !self.requested_previews.insert(published_file_id) {
+                continue;
+            }
+
+            let preview_cache = self.preview_cache.clone();
+            let decoded_previews = self.decoded_previews.clone();
+            self.tokio_rt.spawn(async move {
+                match load_thumbnail(&preview_cache, published_file_id, &url).await {
+                    Ok(thumbnail) => decoded_previews.lock().push((published_file_id, thumbnail)),
+                    Err(e) => warn!("Failed to load preview for {}: {}", published_file_id, e),
+                }
+            });
+        }
+    }
+
+    /// Starts an action on a workshop item through steam, showing its result immediately.
+    /// The result is reverted if steam reports an error.
+    fn perform_item_action(&mut self, published_file_id: u64, action: ItemAction) {
+        type ActionFuture = Pin<
+            Box<
+                dyn Future<Output = Result<Result<(), steamworks::SteamError>, OneShotRecvError>>
+                    + Send,
+            >,
+        >;
+
+        let (steam_client, call_result_poller) =
+            match (self.steam_client.as_ref(), self.call_result_poller.as_ref()) {
+                (Some(steam_client), Some(call_result_poller)) => {
+                    (steam_client, call_result_poller)
+                }
+                _ => {
+                    self.show_toast("Steam is unavailable".into(), true);
+                    return;
+                }
+            };
+
+        let previous_user_state = {
+            let mut workshop_items = self.workshop_items.lock();
+            let workshop_item = match workshop_items
+                .iter_mut()
+                .find(|item| item.published_file_id == published_file_id)
+            {
+                Some(workshop_item) => workshop_item,
+                None => return,
+            };
+
+            let previous_user_state = workshop_item.user_state;
+            action.apply(&mut workshop_item.user_state);
+            previous_user_state
+        };
+
+        info!("{} {}", action.as_str(), published_file_id);
+
+        let id = steamworks::PublishedFileId(published_file_id);
+        let future: ActionFuture = match action {
+            ItemAction::Subscribe => Box::pin(subscribe_item(steam_client, id)),
+            ItemAction::Unsubscribe => Box::pin(unsubscribe_item(steam_client, id)),
+            ItemAction::VoteUp => Box::pin(call_result_poller.set_user_item_vote(id, true)),
+            ItemAction::VoteDown => Box::pin(call_result_poller.set_user_item_vote(id, false)),
+            ItemAction::Favorite => Box::pin(call_result_poller.set_item_favorite(id, true)),
+            ItemAction::Unfavorite => Box::pin(call_result_poller.set_item_favorite(id, false)),
+        };
+
+        let completed_item_actions = self.completed_item_actions.clone();
+        self.tokio_rt.spawn(async move {
+            let result = future.await;
+            completed_item_actions.lock().push(CompletedItemAction {
+                published_file_id,
+                action,
+                previous_user_state,
+                result,
+            });
+        });
+    }
+
+    /// Reports the results of finished item actions, reverting failed ones and resyncing if needed.
+    fn finish_item_actions(&mut self) {
+        let completed_item_actions = std::mem::take(&mut *self.completed_item_actions.lock());
+        for completed in completed_item_actions {
+            let error = match completed.result {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(e) => Some(e.to_string()),
+            };
+
+            match error {
+                None => {
+                    if completed.action.needs_sync() {
+                        self.sync_requested = true;
+                    }
+                    self.show_toast(format!("{} succeeded", completed.action.as_str()), false);
+                }
+                Some(e) => {
+                    error!(
+                        "Failed to {} {}: {}",
+                        completed.action.as_str(),
+                        completed.published_file_id,
+                        e
+                    );
+
+                    if let Some(workshop_item) = self
+                        .workshop_items
+                        .lock()
+                        .iter_mut()
+                        .find(|item| item.published_file_id == completed.published_file_id)
+                    {
+                        workshop_item.user_state = completed.previous_user_state;
+                    }
+                    self.show_toast(format!("{} failed: {}", completed.action.as_str(), e), true);
+                }
+            }
+        }
+    }
+
+    /// Returns true if a publish or update is uploading.
+    fn is_publishing(&self) -> bool {
+        self.publish_state
+            .as_ref()
+            .map_or(false, |publish_state| publish_state.lock().is_publishing())
+    }
+
+    /// Publishes the level in the publish form, unless an upload is already running.
+    fn publish_level(&mut self) {
+        if self.is_publishing() {
+            return;
+        }
+
+        let steam_client = match self.steam_client.as_ref() {
+            Some(steam_client) => steam_client.clone(),
+            None => {
+                self.show_toast("Steam is unavailable".into(), true);
+                return;
+            }
+        };
+
+        self.publish_state = Some(spawn_publish(
+            &self.tokio_rt,
+            steam_client,
+            self.config.get_data_dir().clone(),
+            self.publish_form.to_request(),
+        ));
+        self.reported_publish = false;
+    }
+
+    /// Uploads the level in the update form as a new version of the selected item, unless an upload is already running.
+    fn update_level(&mut self) {
+        if self.is_publishing() {
+            return;
+        }
+
+        let published_file_id = match self.update_form.selected {
+            Some(published_file_id) => published_file_id,
+            None => {
+                self.show_toast("Select a level to update".into(), true);
+                return;
+            }
+        };
+        let title = self
+            .published_items
+            .lock()
+            .iter()
+            .find(|item| item.workshop_item.published_file_id == published_file_id)
+            .map(|item| item.workshop_item.title.clone())
+            .unwrap_or_default();
+
+        let steam_client = match self.steam_client.as_ref() {
+            Some(steam_client) => steam_client.clone(),
+            None => {
+                self.show_toast("Steam is unavailable".into(), true);
+                return;
+            }
+        };
+
+        self.publish_state = Some(spawn_update(
+            &self.tokio_rt,
+            steam_client,
+            self.config.get_data_dir().clone(),
+            UpdateRequest {
+                published_file_id,
+                title,
+                level_path: PathBuf::from(self.update_form.level_path.trim()),
+                change_note: self.update_form.change_note.clone(),
+            },
+        ));
+        self.reported_publish = false;
+    }
+
+    /// Starts querying the user's published items.
+    fn refresh_published_items(&mut self) {
+        let steam_client = match self.steam_client.as_ref() {
+            Some(steam_client) => steam_client,
+            None => {
+                self.show_toast("Steam is unavailable".into(), true);
+                return;
+            }
+        };
+
+        if let Err(e) = spawn_published_items_query(
+            &self.tokio_rt,
+            steam_client,
+            self.config.get_data_dir().clone(),
+            self.published_items.clone(),
+        ) {
+            error!("Failed to query published items: {}", e);
+            self.show_toast(format!("Failed to query published items: {}", e), true);
+        }
+    }
+
+    /// Shows a toast once a publish or update finishes.
+    fn report_publish(&mut self) {
+        if self.reported_publish {
+            return;
+        }
+
+        let message = match self.publish_state.as_ref().map(|state| state.lock()) {
+            Some(publish_state) => match &*publish_state {
+                PublishState::Done(published_file_id, false) => {
+                    Some((format!("Uploaded item {}", published_file_id), false))
+                }
+                PublishState::Done(published_file_id, true) => Some((
+                    format!(
+                        "Uploaded item {}, accept the workshop agreement to make it visible",
+                        published_file_id
+                    ),
+                    false,
+                )),
+                PublishState::Failed(e) => Some((format!("Upload failed: {}", e), true)),
+                _ => None,
+            },
+            None => None,
+        };
+
+        if let Some((message, is_error)) = message {
+            self.reported_publish = true;
+            self.show_toast(message, is_error);
+        }
+    }
+
+    /// Starts rendering a preview of the selected level from its synced file, if it was not rendered yet.
+    fn request_level_preview(&mut self) {
+        let published_file_id = match self.selected_level {
+            Some(published_file_id) => published_file_id,
+            None => return,
+        };
+        let time_updated = match self
+            .workshop_items
+            .lock()
+            .iter()
+            .find(|item| item.published_file_id == published_file_id)
+        {
+            Some(item) => item.time_updated,
+            None => return,
+        };
+        if !self
+            .requested_level_previews
+            .insert((published_file_id, time_updated))
+        {
+            return;
+        }
+
+        let manifest_path = get_sync_manifest_path(self.config.get_data_dir());
+        let unsynced_level_previews = self.unsynced_level_previews.clone();
+        let rendered_level_previews = self.rendered_level_previews.clone();
+        self.tokio_rt.spawn(async move {
+            let manifest = SyncManifest::load(&manifest_path).await;
+            let path = match manifest.items.iter().find(|item| {
+                item.published_file_id == published_file_id && item.time_updated >= time_updated
+            }) {
+                Some(item) => item.path.clone(),
+                None => {
+                    debug!(
+                        "Level {} is not synced yet, rendering its preview after the next sync",
+                        published_file_id
+                    );
+                    unsynced_level_previews
+                        .lock()
+                        .push((published_file_id, time_updated));
+                    return;
+                }
+            };
+
+            let result = tokio::fs::read(&path)
+                .await
+                .map_err(LevelError::from)
+                .and_then(|data| Level::parse_bytes(&data));
+            match result {
+                Ok(level) => rendered_level_previews
+                    .lock()
+                    .push((published_file_id, level.render_preview(PREVIEW_TILE_SIZE))),
+                Err(e) => warn!(
+                    "Failed to render level preview for {}: {}",
+                    published_file_id, e
+                ),
+            }
+        });
+    }
+
+    /// Takes the level previews that finished rendering, so they can be uploaded as textures.
+    pub fn take_rendered_level_previews(&mut self) -> Vec<(u64, RgbaImage)> {
+        std::mem::take(&mut *self.rendered_level_previews.lock())
+    }
+
+    /// Sets the uploaded level preview for a workshop item.
+    /// Returns the preview it replaced, if any, so its texture can be removed.
+    pub fn insert_level_preview_image(
+        &mut self,
+        published_file_id: u64,
+        image_id: conrod_core::image::Id,
+    ) -> Option<conrod_core::image::Id> {
+        self.level_preview_images
+            .insert(published_file_id, image_id)
+    }
+
+    /// Takes the thumbnails that finished loading, so they can be uploaded as textures.
+    pub fn take_decoded_previews(&mut self) -> Vec<(u64, RgbaImage)> {
+        std::mem::take(&mut *self.decoded_previews.lock())
+    }
+
+    /// Sets the uploaded thumbnail for a workshop item.
+    pub fn insert_preview_image(
+        &mut self,
+        published_file_id: u64,
+        image_id: conrod_core::image::Id,
+    ) {
+        self.preview_images.insert(published_file_id, image_id);
+    }
+
+    /// Rescans the sync dir and library folders for level files in the background.
+    fn refresh_library(&mut self) {
+        let sync_dir = self.config.get_workshop_sync_path().clone();
+        let folders = self.library.folders.clone();
+        let completed_library_tasks = self.completed_library_tasks.clone();
+        self.tokio_rt.spawn(async move {
+            let result = scan_levels(&sync_dir, &folders).await;
+            completed_library_tasks
+                .lock()
+                .push(LibraryTaskResult::Scanned(result));
+        });
+    }
+
+    /// Saves the library once it has not changed for LIBRARY_SAVE_DELAY.
+    fn save_library(&mut self) {
+        self.library_save_at = Some(Instant::now() + LIBRARY_SAVE_DELAY);
+    }
+
+    /// Starts saving the library in the background if a save is due.
+    /// Saves don't overlap, so an older one can't finish last.
+    fn save_library_if_due(&mut self) {
+        let is_due = self
+            .library_save_at
+            .map_or(false, |save_at| Instant::now() >= save_at);
+        if !is_due || self.is_saving_library {
+            return;
+        }
+        self.library_save_at = None;
+        self.is_saving_library = true;
+
+        let library = self.library.clone();
+        let data_dir = self.config.get_data_dir().clone();
+        let completed_library_tasks = self.completed_library_tasks.clone();
+        self.tokio_rt.spawn(async move {
+            let result = library.save(&data_dir).await;
+            completed_library_tasks
+                .lock()
+                .push(LibraryTaskResult::Saved(result));
+        });
+    }
+
+    /// Saves the library right away if it has unsaved changes. Called before the launcher exits.
+    pub fn flush_library(&mut self) {
+        if self.library_save_at.take().is_none() && !self.is_saving_library {
+            return;
+        }
+
+        let data_dir = self.config.get_data_dir().clone();
+        if let Err(e) = self.tokio_rt.block_on(self.library.save(&data_dir)) {
+            error!("Failed to save library: {}", e);
+        }
+    }
+
+    /// Reports the results of finished library scans, saves and exports.
+    fn finish_library_tasks(&mut self) {
+        let completed_library_tasks = std::mem::take(&mut *self.completed_library_tasks.lock());
+        for completed in completed_library_tasks {
+            match completed {
+                LibraryTaskResult::Scanned(Ok(library_levels)) => {
+                    self.library_levels = library_levels;
+                }
+                LibraryTaskResult::Scanned(Err(e)) => {
+                    error!("Failed to scan library: {}", e);
+                    self.show_toast(format!("Failed to scan library: {}", e), true);
+                }
+                LibraryTaskResult::Saved(result) => {
+                    self.is_saving_library = false;
+                    if let Err(e) = result {
+                        error!("Failed to save library: {}", e);
+                        self.show_toast(format!("Failed to save library: {}", e), true);
+                    }
+                }
+                LibraryTaskResult::Exported {
+                    name,
+                    export_dir,
+                    result: Ok(paths),
+                } => {
+                    info!("Exported playlist '{}' to '{}'", name, export_dir.display());
+                    self.show_toast(
+                        format!(
+                            "Exported {} level(s) to '{}'",
+                            paths.len(),
+                            export_dir.display()
+                        ),
+                        false,
+                    );
+                }
+                LibraryTaskResult::Exported { result: Err(e), .. } => {
+                    error!("Failed to export playlist: {}", e);
+                    self.show_toast(format!("Failed to export playlist: {}", e), true);
+                }
+            }
+        }
+    }
+
+    /// Selects a level in the library view, filling the form with its info.
+    fn select_library_level(&mut self, path: PathBuf) {
+        let info = self.library.get_info(&path);
+        self.library_form.tags = info.tags.join(", ");
+        self.library_form.notes = info.notes;
+        self.library_form.selected_level = Some(path);
+    }
+
+    /// Stores the tags and notes in the form for the selected level.
+    fn update_library_level_info(&mut self) {
+        let path = match self.library_form.selected_level.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut info = self.library.get_info(path);
+        info.tags = self
+            .library_form
+            .tags
+            .split(',')
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect();
+        info.notes = self.library_form.notes.clone();
+        self.library.set_info(path, info);
+        self.save_library();
+    }
+
+    /// Marks or unmarks the selected level as a favorite.
+    fn set_library_favorite(&mut self, favorite: bool) {
+        let path = match self.library_form.selected_level.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut info = self.library.get_info(path);
+        info.favorite = favorite;
+        self.library.set_info(path, info);
+        self.save_library();
+    }
+
+    /// Adds the folder in the form to the library.
+    fn add_library_folder(&mut self) {
+        let folder = PathBuf::from(self.library_form.folder.trim());
+        if folder.as_os_str().is_empty() {
+            return;
+        }
+        if !folder.is_dir() {
+            self.show_toast(format!("'{}' is not a folder", folder.display()), true);
+            return;
+        }
+
+        if !self.library.folders.contains(&folder) {
+            self.library.folders.push(folder);
+            self.save_library();
+        }
+        self.library_form.folder.clear();
+        self.refresh_library();
+    }
+
+    /// Creates a playlist with the name in the form and selects it.
+    fn create_playlist(&mut self) {
+        match self.library.add_playlist(&self.library_form.playlist_name) {
+            Ok(i) => {
+                self.library_form.selected_playlist = Some(i);
+                self.library_form.selected_playlist_level = None;
+                self.library_form.playlist_name.clear();
+                self.save_library();
+            }
+            Err(e) => self.show_toast(format!("Failed to create playlist: {}", e), true),
+        }
+    }
+
+    /// Deletes the selected playlist.
+    fn delete_playlist(&mut self) {
+        let i = match self.library_form.selected_playlist.take() {
+            Some(i) if i < self.library.playlists.len() => i,
+            _ => return,
+        };
+
+        self.library.playlists.remove(i);
+        self.library_form.selected_playlist_level = None;
+        self.save_library();
+    }
+
+    /// Appends the selected level to the selected playlist.
+    fn add_to_playlist(&mut self) {
+        let path = match self.library_form.selected_level.as_ref() {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        let playlist = match self
+            .library_form
+            .selected_playlist
+            .and_then(|i| self.library.playlists.get_mut(i))
+        {
+            Some(playlist) => playlist,
+            None => {
+                self.show_toast("Select or create a playlist first".into(), true);
+                return;
+            }
+        };
+
+        playlist.levels.push(path);
+        self.save_library();
+    }
+
+    /// Moves the selected level of the selected playlist up or down.
+    fn move_playlist_level(&mut self, up: bool) {
+        let (playlist, i) = match (
+            self.library_form
+                .selected_playlist
+                .and_then(|i| self.library.playlists.get_mut(i)),
+            self.library_form.selected_playlist_level,
+        ) {
+            (Some(playlist), Some(i)) => (playlist, i),
+            _ => return,
+        };
+
+        if up {
+            playlist.move_up(i);
+            self.library_form.selected_playlist_level = Some(i.saturating_sub(1));
+        } else {
+            playlist.move_down(i);
+            self.library_form.selected_playlist_level =
+                Some((i + 1).min(playlist.levels.len().saturating_sub(1)));
+        }
+        self.save_library();
+    }
+
+    /// Removes the selected level from the selected playlist.
+    fn remove_playlist_level(&mut self) {
+        let (playlist, i) = match (
+            self.library_form
+                .selected_playlist
+                .and_then(|i| self.library.playlists.get_mut(i)),
+            self.library_form.selected_playlist_level.take(),
+        ) {
+            (Some(playlist), Some(i)) if i < playlist.levels.len() => (playlist, i),
+            _ => return,
+        };
+
+        playlist.levels.remove(i);
+        self.save_library();
+    }
+
+    /// Copies the levels of the selected playlist, in order, to the export folder in the form.
+    fn export_playlist(&mut self) {
+        let export_dir = PathBuf::from(self.library_form.export_dir.trim());
+        if export_dir.as_os_str().is_empty() {
+            self.show_toast("Enter a folder to export to".into(), true);
+            return;
+        }
+        let playlist = match self
+            .library_form
+            .selected_playlist
+            .and_then(|i| self.library.playlists.get(i))
+        {
+            Some(playlist) => playlist.clone(),
+            None => return,
+        };
+
+        let completed_library_tasks = self.completed_library_tasks.clone();
+        self.tokio_rt.spawn(async move {
+            let result = playlist.export(&export_dir).await;
+            completed_library_tasks
+                .lock()
+                .push(LibraryTaskResult::Exported {
+                    name: playlist.name,
+                    export_dir,
+                    result,
+                });
+        });
+    }
+
+    /// Exports the synced levels of the items in the pack selection as a pack in the data dir.
+    /// The pack is written in the background and reported with a toast.
+    fn export_pack(&mut self) {
+        if self.pack_selection.is_empty() {
+            self.show_toast("Add levels to the pack first".into(), true);
+            return;
+        }
+
+        let data_dir = self.config.get_data_dir().clone();
+        let published_file_ids = self.pack_selection.clone();
+        let workshop_items = self.workshop_items.clone();
+        let completed_pack_tasks = self.completed_pack_tasks.clone();
+        self.tokio_rt.spawn(async move {
+            let manifest = SyncManifest::load(&get_sync_manifest_path(&data_dir)).await;
+            let paths: Vec<PathBuf> = published_file_ids
+                .iter()
+                .flat_map(|published_file_id| {
+                    manifest
+                        .items
+                        .iter()
+                        .filter(move |item| item.published_file_id == *published_file_id)
+                        .map(|item| item.path.clone())
+                })
+                .collect();
+            let sources = get_pack_sources(&paths, &manifest, &workshop_items.lock());
+
+            let path = get_default_pack_path(&data_dir);
+            let export_path = path.clone();
+            let result = spawn_pack_task(move || export_pack(&export_path, &sources)).await;
+            completed_pack_tasks.lock().push(PackTaskResult::Exported {
+                path,
+                published_file_ids,
+                result,
+            });
+        });
+    }
+
+    /// Installs a pack into the sync dir. Called when a file is dropped on the window.
+    /// The pack is read and installed in the background and reported with a toast.
+    pub fn import_pack(&mut self, path: &Path) {
+        let path = path.to_path_buf();
+        let sync_dir = self.config.get_workshop_sync_path().clone();
+        let completed_pack_tasks = self.completed_pack_tasks.clone();
+        self.tokio_rt.spawn(async move {
+            let import_path = path.clone();
+            let result = spawn_pack_task(move || import_pack(&import_path, &sync_dir)).await;
+            completed_pack_tasks
+                .lock()
+                .push(PackTaskResult::Imported { path, result });
+        });
+    }
+
+    /// Reports the results of finished pack exports and imports.
+    fn finish_pack_tasks(&mut self) {
+        let completed_pack_tasks = std::mem::take(&mut *self.completed_pack_tasks.lock());
+        for completed in completed_pack_tasks {
+            match completed {
+                PackTaskResult::Exported {
+                    path,
+                    published_file_ids,
+                    result: Ok(pack_manifest),
+                } => {
+                    info!("Exported pack to '{}'", path.display());
+                    self.show_toast(
+                        format!(
+                            "Exported {} level(s) to '{}'",
+                            pack_manifest.levels.len(),
+                            path.display()
+                        ),
+                        false,
+                    );
+
+                    // Levels added while the pack was written stay selected for the next one.
+                    self.pack_selection.retain(|published_file_id| {
+                        !published_file_ids.contains(published_file_id)
+                    });
+                }
+                PackTaskResult::Exported { result: Err(e), .. } => {
+                    error!("Failed to export pack: {}", e);
+                    self.show_toast(format!("Failed to export pack: {}", e), true);
+                }
+                PackTaskResult::Imported {
+                    path,
+                    result: Ok(levels),
+                } => {
+                    info!(
+                        "Installed {} level(s) from '{}'",
+                        levels.len(),
+                        path.display()
+                    );
+                    self.show_toast(
+                        format!(
+                            "Installed {} level(s) from '{}'",
+                            levels.len(),
+                            path.display()
+                        ),
+                        false,
+                    );
+                    self.refresh_library();
+                }
+                PackTaskResult::Imported {
+                    path,
+                    result: Err(e),
+                } => {
+                    error!("Failed to import pack '{}': {}", path.display(), e);
+                    self.show_toast(format!("Failed to import pack: {}", e), true);
+                }
+            }
+        }
+    }
+
+    /// Shows a toast, replacing the current one.
+    pub fn show_toast(&mut self, message: String, is_error: bool) {
+        self.toast = Some(Toast::new(message, is_error));
+    }
+
+    /// Swaps in the config file if it changed and is valid, keeping the old config otherwise.
+    fn reload_config(&mut self) {
+        let result = match self.config_watcher.as_ref().and_then(|w| w.poll()) {
+            Some(result) => result,
+            None => return,
+        };
+
+        match result {
+            Ok(config) => {
+                info!("Reloaded config");
+                crate::logger::set_level(config.log.level);
+                crate::crash::set_config(&config);
+                self.config = config;
+                self.show_toast("Reloaded config".into(), false);
+            }
+            Err(e) => {
+                error!("Failed to reload config: {}", e);
+                self.show_toast(format!("Invalid config, keeping old one: {}", e), true);
+            }
+        }
+    }
+
+    /// Exports a diagnostics bundle to the diagnostics dir.
+    fn export_diagnostics(&mut self) {
+        let path = crate::diagnostics::get_default_bundle_path(self.config.get_data_dir());
+        match crate::diagnostics::export_bundle(
+            &path,
+            &self.config,
+            Some(&self.gpu_info),
+            self.redact_diagnostics,
+        ) {
+            Ok(()) => {
+                info!("Exported diagnostics to '{}'", path.display());
+                self.show_toast(format!("Saved '{}'", path.display()), false);
+            }
+            Err(e) => {
+                error!("Failed to export diagnostics: {}", e);
+                self.show_toast(format!("Failed to export diagnostics: {}", e), true);
+            }
+        }
+    }
+
+    /// Opens the pending crash report and marks it as seen.
+    fn open_crash_report(&mut self) {
+        if let Some(path) = self.pending_crash_report.as_ref() {
+            crate::util::open_program(&*path.to_string_lossy());
+        }
+        self.dismiss_crash_report();
+    }
+
+    /// Zips the pending crash report with the logs and marks it as seen.
+    fn zip_crash_report(&mut self) {
+        let path = match self.pending_crash_report.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+
+        match crate::crash::zip_crash_report(path, self.config.get_data_dir()) {
+            Ok(zip_path) => {
+                info!("Zipped crash report to '{}'", zip_path.display());
+                self.show_toast(format!("Saved '{}'", zip_path.display()), false);
+                self.dismiss_crash_report();
+            }
+            Err(e) => {
+                error!("Failed to zip crash report: {}", e);
+                self.show_toast(format!("Failed to zip crash report: {}", e), true);
+            }
+        }
+    }
+
+    /// Marks the pending crash report as seen.
+    fn dismiss_crash_report(&mut self) {
+        if let Err(e) = crate::crash::clear_pending_crash_report(self.config.get_data_dir()) {
+            error!("Failed to clear pending crash report: {}", e);
+        }
+        self.pending_crash_report = None;
+    }
 }
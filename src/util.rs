@@ -1,3 +1,8 @@
+use log::info;
+use sha2::{
+    Digest,
+    Sha256,
+};
 use std::ffi::CString;
 
 /// Executes a cmd. cmd must contain no 0s.
@@ -10,5 +15,94 @@ pub fn system<T: Into<Vec<u8>>>(cmd: T) {
 }
 
 pub fn open_program(path: &str) {
+    info!("Opening '{}'", path);
     system(format!("\"{}\"", path));
 }
+
+/// Characters that can't be in a file name on windows. This includes the path separators of every platform.
+const INVALID_FILE_NAME_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Names windows reserves for devices. They can't be used as file names, even with an extension.
+const RESERVED_FILE_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Makes a name, like a workshop title, safe to use as a single file name on any platform.
+///
+/// Separators and other invalid characters are replaced with `_` and trailing dots and spaces are removed,
+/// so the name can never be `.`, `..` or a path. Empty and reserved names get a `_` in front.
+pub fn sanitize_file_name(name: &str) -> String {
+    let mut name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || INVALID_FILE_NAME_CHARS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let len = name.trim_end_matches(|c| c == '.' || c == ' ').len();
+    name.truncate(len);
+
+    let stem = name.split('.').next().unwrap_or("").trim_end();
+    if name.is_empty()
+        || RESERVED_FILE_NAMES
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        name.insert(0, '_');
+    }
+
+    name
+}
+
+/// Gets the sha256 of the data as a lowercase hex string.
+pub fn get_checksum(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Makes an empty dir in the temp dir for a test.
+#[cfg(test)]
+pub fn get_test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "skeleton-sprint-launcher-{}-{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("failed to create test dir");
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_file_name_keeps_plain_names() {
+        assert_eq!(sanitize_file_name("My Level"), "My Level");
+        assert_eq!(sanitize_file_name("Level 2.0 (hard)"), "Level 2.0 (hard)");
+        assert_eq!(sanitize_file_name("Über-Level"), "Über-Level");
+    }
+
+    #[test]
+    fn sanitize_file_name_removes_paths() {
+        assert_eq!(sanitize_file_name("a/b\\c"), "a_b_c");
+        assert_eq!(sanitize_file_name("../../x"), ".._.._x");
+        assert_eq!(sanitize_file_name(".."), "_");
+        assert_eq!(sanitize_file_name("."), "_");
+        assert_eq!(sanitize_file_name(""), "_");
+        assert_eq!(sanitize_file_name("level. . "), "level");
+        assert_eq!(sanitize_file_name("C:level?\n"), "C_level__");
+    }
+
+    #[test]
+    fn sanitize_file_name_avoids_reserved_names() {
+        assert_eq!(sanitize_file_name("con"), "_con");
+        assert_eq!(sanitize_file_name("NUL.level"), "_NUL.level");
+        assert_eq!(sanitize_file_name("Lpt1 "), "_Lpt1");
+        assert_eq!(sanitize_file_name("Console"), "Console");
+    }
+}
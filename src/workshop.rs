@@ -0,0 +1,357 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    cmp::Ordering,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// Metadata for a workshop item, as shown in the level list.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WorkshopItem {
+    pub published_file_id: u64,
+    pub title: String,
+    pub description: String,
+    pub author: String,
+    pub tags: Vec<String>,
+    pub score: f32,
+    pub num_upvotes: u32,
+    pub num_downvotes: u32,
+    pub time_updated: u32,
+    pub preview_url: Option<String>,
+
+    #[serde(default)]
+    pub user_state: WorkshopItemUserState,
+
+    /// True if the item came from the cache and was missing from the last query.
+    #[serde(skip)]
+    pub is_stale: bool,
+
+    /// Why the item was quarantined instead of synced, if it is not a valid level.
+    #[serde(skip)]
+    pub invalid_reason: Option<String>,
+}
+
+/// The current user's relation to a workshop item.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WorkshopItemUserState {
+    pub subscribed: bool,
+
+    /// Some(true) if voted up, Some(false) if voted down.
+    pub vote: Option<bool>,
+
+    pub favorited: bool,
+}
+
+impl WorkshopItem {
+    /// Makes a WorkshopItem from a query result.
+    /// The author's name and preview url are not part of the result and must be looked up separately.
+    pub fn from_query_result(
+        result: &steamworks::QueryResult,
+        author: String,
+        preview_url: Option<String>,
+    ) -> Self {
+        Self {
+            published_file_id: result.published_file_id.0,
+            title: result.title.clone(),
+            description: result.description.clone(),
+            author,
+            tags: result.tags.clone(),
+            score: result.score,
+            num_upvotes: result.num_upvotes,
+            num_downvotes: result.num_downvotes,
+            time_updated: result.time_updated,
+            preview_url,
+            user_state: WorkshopItemUserState::default(),
+            is_stale: false,
+            invalid_reason: None,
+        }
+    }
+
+    /// Returns true if the filter is a case-insensitive substring of the title, author, description or a tag.
+    /// The filter must already be lowercase.
+    fn matches(&self, filter: &str) -> bool {
+        filter.is_empty()
+            || self.title.to_lowercase().contains(filter)
+            || self.author.to_lowercase().contains(filter)
+            || self.description.to_lowercase().contains(filter)
+            || self
+                .tags
+                .iter()
+                .any(|tag| tag.to_lowercase().contains(filter))
+    }
+}
+
+/// The workshop items from the last successful query, so the level list can be shown before steam responds.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct WorkshopCache {
+    pub items: Vec<WorkshopItem>,
+}
+
+impl WorkshopCache {
+    /// Gets the path of the cache in the data dir.
+    pub fn get_path(data_dir: &Path) -> PathBuf {
+        data_dir.join("workshop_cache.json")
+    }
+
+    /// Loads the cache, returning an empty one if it does not exist.
+    pub fn load(data_dir: &Path) -> Result<Self, std::io::Error> {
+        match std::fs::read(Self::get_path(data_dir)) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Saves the cache to the data dir.
+    pub async fn save(&self, data_dir: &Path) -> Result<(), std::io::Error> {
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        tokio::fs::create_dir_all(data_dir).await?;
+        tokio::fs::write(Self::get_path(data_dir), data).await
+    }
+}
+
+/// Something the user can do to a workshop item from the level list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemAction {
+    Subscribe,
+    Unsubscribe,
+    VoteUp,
+    VoteDown,
+    Favorite,
+    Unfavorite,
+}
+
+impl ItemAction {
+    /// Gets a user-facing name for this action.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Subscribe => "Subscribe",
+            Self::Unsubscribe => "Unsubscribe",
+            Self::VoteUp => "Vote Up",
+            Self::VoteDown => "Vote Down",
+            Self::Favorite => "Favorite",
+            Self::Unfavorite => "Unfavorite",
+        }
+    }
+
+    /// Updates the user state to what it will be once the action succeeds.
+    pub fn apply(self, user_state: &mut WorkshopItemUserState) {
+        match self {
+            Self::Subscribe => user_state.subscribed = true,
+            Self::Unsubscribe => user_state.subscribed = false,
+            Self::VoteUp => user_state.vote = Some(true),
+            Self::VoteDown => user_state.vote = Some(false),
+            Self::Favorite => user_state.favorited = true,
+            Self::Unfavorite => user_state.favorited = false,
+        }
+    }
+
+    /// Returns true if the action changes which items should be synced.
+    pub fn needs_sync(self) -> bool {
+        matches!(self, Self::Subscribe | Self::Unsubscribe)
+    }
+}
+
+/// How to sort the level list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkshopItemSort {
+    /// Alphabetical by title
+    Title,
+
+    /// Most recently updated first
+    LastUpdated,
+
+    /// Highest score first
+    Score,
+
+    /// Alphabetical by author
+    Author,
+}
+
+impl WorkshopItemSort {
+    /// All sort orders, in the order they are shown in the ui.
+    pub const ALL: [Self; 4] = [Self::Title, Self::LastUpdated, Self::Score, Self::Author];
+
+    /// Gets a user-facing name for this sort order.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Title => "Title",
+            Self::LastUpdated => "Last Updated",
+            Self::Score => "Score",
+            Self::Author => "Author",
+        }
+    }
+
+    fn compare(self, a: &WorkshopItem, b: &WorkshopItem) -> Ordering {
+        match self {
+            Self::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            Self::LastUpdated => b.time_updated.cmp(&a.time_updated),
+            Self::Score => b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal),
+            Self::Author => a.author.to_lowercase().cmp(&b.author.to_lowercase()),
+        }
+    }
+}
+
+/// Gets the indices of the items that match the filter, in sorted order.
+pub fn filter_and_sort(items: &[WorkshopItem], filter: &str, sort: WorkshopItemSort) -> Vec<usize> {
+    let filter = filter.trim().to_lowercase();
+
+    let mut indices: Vec<usize> = items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.matches(&filter))
+        .map(|(i, _)| i)
+        .collect();
+    indices.sort_by(|a, b| sort.compare(&items[*a], &items[*b]));
+
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::get_test_dir;
+
+    fn make_item(published_file_id: u64, title: &str, author: &str) -> WorkshopItem {
+        WorkshopItem {
+            published_file_id,
+            title: title.into(),
+            description: String::new(),
+            author: author.into(),
+            tags: Vec::new(),
+            score: 0.0,
+            num_upvotes: 0,
+            num_downvotes: 0,
+            time_updated: 0,
+            preview_url: None,
+            user_state: WorkshopItemUserState::default(),
+            is_stale: false,
+            invalid_reason: None,
+        }
+    }
+
+    fn make_items() -> Vec<WorkshopItem> {
+        let mut items = vec![
+            make_item(1, "Spikes", "carol"),
+            make_item(2, "bridge", "Alice"),
+            make_item(3, "Caves", "bob"),
+        ];
+        items[0].time_updated = 200;
+        items[0].score = 0.5;
+        items[0].tags = vec!["Hard".into()];
+        items[1].time_updated = 300;
+        items[1].score = 0.9;
+        items[2].time_updated = 100;
+        items[2].score = 0.1;
+        items[2].description = "A long level in the dark".into();
+        items
+    }
+
+    #[test]
+    fn filter_matches_any_field() {
+        let items = make_items();
+        let sort = WorkshopItemSort::Title;
+        assert_eq!(filter_and_sort(&items, "", sort), vec![1, 2, 0]);
+        assert_eq!(filter_and_sort(&items, "  ", sort), vec![1, 2, 0]);
+        assert_eq!(filter_and_sort(&items, "SPIKES", sort), vec![0]);
+        assert_eq!(filter_and_sort(&items, "alice", sort), vec![1]);
+        assert_eq!(filter_and_sort(&items, " dark ", sort), vec![2]);
+        assert_eq!(filter_and_sort(&items, "hard", sort), vec![0]);
+        assert_eq!(filter_and_sort(&items, "ca", sort), vec![2, 0]);
+        assert!(filter_and_sort(&items, "missing", sort).is_empty());
+    }
+
+    #[test]
+    fn sort_orders() {
+        let items = make_items();
+        assert_eq!(
+            filter_and_sort(&items, "", WorkshopItemSort::Title),
+            vec![1, 2, 0]
+        );
+        assert_eq!(
+            filter_and_sort(&items, "", WorkshopItemSort::LastUpdated),
+            vec![1, 0, 2]
+        );
+        assert_eq!(
+            filter_and_sort(&items, "", WorkshopItemSort::Score),
+            vec![1, 0, 2]
+        );
+        assert_eq!(
+            filter_and_sort(&items, "", WorkshopItemSort::Author),
+            vec![1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn sort_keeps_order_of_ties() {
+        let items = vec![
+            make_item(1, "Level", "b"),
+            make_item(2, "level", "a"),
+            make_item(3, "LEVEL", "c"),
+        ];
+        assert_eq!(
+            filter_and_sort(&items, "", WorkshopItemSort::Title),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            filter_and_sort(&items, "", WorkshopItemSort::Score),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let data_dir = get_test_dir("workshop-cache");
+        let mut items = make_items();
+        items[0].user_state.vote = Some(true);
+        items[1].preview_url = Some("https://example.com/preview.png".into());
+        items[2].is_stale = true;
+        items[2].invalid_reason = Some("Invalid".into());
+
+        let mut tokio_rt = tokio::runtime::Runtime::new().expect("failed to start tokio");
+        tokio_rt
+            .block_on(WorkshopCache { items }.save(&data_dir))
+            .expect("failed to save cache");
+
+        let cache = WorkshopCache::load(&data_dir).expect("failed to load cache");
+        let ids: Vec<u64> = cache
+            .items
+            .iter()
+            .map(|item| item.published_file_id)
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(cache.items[0].user_state.vote, Some(true));
+        assert_eq!(cache.items[0].tags, vec![String::from("Hard")]);
+        assert_eq!(
+            cache.items[1].preview_url.as_deref(),
+            Some("https://example.com/preview.png")
+        );
+
+        // Whether an item is stale or invalid is found again on every sync.
+        assert!(!cache.items[2].is_stale);
+        assert_eq!(cache.items[2].invalid_reason, None);
+
+        std::fs::remove_dir_all(&data_dir).expect("failed to remove test dir");
+    }
+
+    #[test]
+    fn cache_load_missing_or_invalid() {
+        let data_dir = get_test_dir("workshop-cache-invalid");
+        let cache = WorkshopCache::load(&data_dir).expect("failed to load missing cache");
+        assert!(cache.items.is_empty());
+
+        std::fs::write(WorkshopCache::get_path(&data_dir), "not json")
+            .expect("failed to write cache");
+        let error = WorkshopCache::load(&data_dir).expect_err("invalid cache was loaded");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        std::fs::remove_dir_all(&data_dir).expect("failed to remove test dir");
+    }
+}